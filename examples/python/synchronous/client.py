@@ -11,23 +11,22 @@ grid_client = SyncGridClient("[::1]:50051", "a nice client")
 service_id = 0
 service_version = 0
 
-jobs_per_id = dict()
+# Create the jobs.
+jobs = [{"a": 1.0, "b": 2.0 * i} for i in range(0, 100)]
 
-for i in range(0, 100):
-    # Create a job.
-    job = {"a": 1.0, "b": 2.0 * i}
+# Submit the jobs at once.
+job_ids = grid_client.client_submit_jobs(
+    service_id, service_version, (pickle.dumps(job) for job in jobs)
+)
 
-    # Submit the job.
-    job_id = grid_client.submit_job(service_id, service_version, pickle.dumps(job))
-
-    jobs_per_id[job_id] = job
+jobs_per_id = dict(zip(job_ids, jobs))
 
 print(f"Created {len(jobs_per_id)} jobs")
 print("Waiting for results ...")
 
 while jobs_per_id:
     # Fetch results
-    results = grid_client.fetch_results()
+    results = grid_client.client_fetch_results()
 
     if not results:
         time.sleep(1.0)
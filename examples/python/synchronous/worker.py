@@ -3,10 +3,14 @@ A grid worker.
 """
 from grid import Result, SyncGridClient
 import pickle
-import time
+
+# The worker role requires the secret that the grid server is given with
+# `--role-secret-file worker=worker_secret`.
+with open("worker_secret") as role_secret_file:
+    role_secret = role_secret_file.read().strip()
 
 # Connect to the server.
-grid_client = SyncGridClient("[::1]:50051", "worker")
+grid_client = SyncGridClient("[::1]:50051", "worker", role="worker", role_secret=role_secret)
 
 print("Processing jobs ...")
 
@@ -16,12 +20,17 @@ service_version = 0
 result = None
 
 while True:
-    # Fetch a job and maybe send a result.
-    job = grid_client.worker_server_exchange(service_id, service_version, result)
+    # Fetch a job and maybe send a result. Wait up to 10 seconds for a job to be queued.
+    job, cancelled_job_ids = grid_client.worker_server_exchange(
+        service_id, service_version, result, 10000
+    )
+
+    # Each job is processed before the next exchange, so a cancelled job was already processed.
+    for job_id in cancelled_job_ids:
+        print(f"Job {job_id} was cancelled")
 
     if not job:
         result = None
-        time.sleep(1.0)
         continue
 
     job_data = pickle.loads(job.job_data)
@@ -1,24 +1,40 @@
 use grid_manager_interface::grid_manager_client::GridManagerClient;
 use grid_manager_interface::{
-    ClientId, RequestAcceptServiceLibrary, RequestGetStatus, RequestServerStart, RequestServerStop,
-    RequestWorkerStart, RequestWorkerStop, ResponseAcceptServiceLibrary, ResponseGetStatus,
-    ResponseServerStart, ResponseServerStop, ResponseWorkerStart, ResponseWorkerStop,
-    ServerConfiguration, ServiceLibraryConfiguration, WorkerConfiguration,
+    ClientId, CompressionEncoding, RequestAcceptServiceLibrary, RequestGetStatus,
+    RequestServerStart, RequestServerStop, RequestWorkerStart, RequestWorkerStop,
+    ResponseAcceptServiceLibrary, ResponseGetStatus, ResponseServerStart, ResponseServerStop,
+    ResponseWorkerStart, ResponseWorkerStop, ServerConfiguration, ServiceLibraryConfiguration,
+    WorkerConfiguration,
 };
+use grid_server_interface::TlsPaths;
 use tonic::transport::Channel;
 use tonic::{Request, Response, Status};
 
-///
+/// A client of the grid manager.
 pub struct AsyncManagerClient {
     client_id: ClientId,
     grid_client: GridManagerClient<Channel>,
 }
 
+/// Connects to the grid manager at the given address. The requests are compressed with the given
+/// encoding, if any, which the grid manager always accepts. The responses are compressed if the
+/// grid manager is configured to.
 ///
+/// The connection uses TLS if the given paths contain a CA certificate, see `TlsPaths`.
 pub async fn connect_async_manager_client(
     server_address: &str,
+    compression: Option<CompressionEncoding>,
+    tls_paths: &TlsPaths,
 ) -> Result<AsyncManagerClient, Box<dyn std::error::Error>> {
-    let grid_client = GridManagerClient::connect(format!("http://{}", server_address)).await?;
+    let channel = tls_paths.endpoint(server_address)?.connect().await?;
+
+    let mut grid_client = GridManagerClient::new(channel)
+        .accept_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Zstd);
+
+    if let Some(compression) = compression {
+        grid_client = grid_client.send_compressed(compression);
+    }
 
     // TODO
     /*
@@ -39,7 +55,8 @@ pub async fn connect_async_manager_client(
 }
 
 impl AsyncManagerClient {
-    ///
+    /// Sends the given library of the given service version to the grid manager, which stores it
+    /// for the grid workers it starts.
     pub async fn accept_service_library(
         &mut self,
         service_id: u32,
@@ -58,7 +75,7 @@ impl AsyncManagerClient {
             .await
     }
 
-    ///
+    /// Returns the grid servers and grid workers that the grid manager started.
     pub async fn get_status(&mut self) -> Result<Response<ResponseGetStatus>, Status> {
         self.grid_client
             .get_status(Request::new(RequestGetStatus {
@@ -67,7 +84,7 @@ impl AsyncManagerClient {
             .await
     }
 
-    ///
+    /// Lets the grid manager start a grid server on the given address.
     pub async fn start_server(
         &mut self,
         server_address: String,
@@ -80,7 +97,8 @@ impl AsyncManagerClient {
             .await
     }
 
-    ///
+    /// Lets the grid manager start a grid worker for the given service version, which connects to
+    /// the grid server at the given address.
     pub async fn start_worker(
         &mut self,
         server_address: String,
@@ -101,7 +119,7 @@ impl AsyncManagerClient {
             .await
     }
 
-    ///
+    /// Lets the grid manager stop the grid server with the given process ID.
     pub async fn stop_server(
         &mut self,
         server_pid: u64,
@@ -114,7 +132,7 @@ impl AsyncManagerClient {
             .await
     }
 
-    ///
+    /// Lets the grid manager stop the grid worker with the given process ID.
     pub async fn stop_worker(
         &mut self,
         worker_pid: u64,
@@ -1,12 +1,17 @@
 mod client;
 
 use crate::client::connect_async_manager_client;
+use grid_manager_interface::CompressionEncoding;
+use grid_server_interface::{TlsPaths, TLS_USAGE};
 use std::env::args;
 use std::fs::read;
 use std::process::exit;
 
-fn print_usage_and_stop() {
-    println!("Usage: `grid-config <MANAGER_ADDRESS> <COMMAND>`");
+fn print_usage_and_stop() -> ! {
+    println!(
+        "Usage: `grid-config [--compression gzip|zstd|none] <MANAGER_ADDRESS> <COMMAND> \
+         {TLS_USAGE}`"
+    );
     println!("Example:");
     println!("  grid-config 127.0.0.1:50000 upload 0 42 service_library.dll");
     println!("  grid-config 127.0.0.1:50000 status");
@@ -22,7 +27,31 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
 
     // Get the given command line arguments.
-    let command_line_arguments: Vec<_> = args().collect();
+    let mut command_line_arguments: Vec<_> = args().collect();
+
+    // Take the compression of the requests, e.g. of the uploaded service libraries, from the
+    // command line arguments. They are compressed with gzip if not given.
+    let compression = if command_line_arguments.get(1).map(String::as_str) == Some("--compression")
+    {
+        let compression = match command_line_arguments.get(2).map(String::as_str) {
+            Some("gzip") => Some(CompressionEncoding::Gzip),
+            Some("zstd") => Some(CompressionEncoding::Zstd),
+            Some("none") => None,
+            _ => print_usage_and_stop(),
+        };
+
+        command_line_arguments.drain(1..3);
+        compression
+    } else {
+        Some(CompressionEncoding::Gzip)
+    };
+
+    // The paths for connecting over TLS.
+    let tls_paths = TlsPaths::take_from_command_line_arguments(&mut command_line_arguments)
+        .unwrap_or_else(|error_message| {
+            eprintln!("{error_message}");
+            print_usage_and_stop()
+        });
 
     // Too few command line arguments are given.
     if command_line_arguments.len() <= 2 {
@@ -33,7 +62,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let command = &command_line_arguments[2];
 
     // Try to connect to the server.
-    let mut manager_client = connect_async_manager_client(manager_address).await?;
+    let mut manager_client =
+        connect_async_manager_client(manager_address, compression, &tls_paths).await?;
 
     match command.as_ref() {
         "status" => {
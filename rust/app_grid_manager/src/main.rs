@@ -2,15 +2,15 @@
 extern crate log;
 
 use grid_manager_interface::{
-    GridManager, GridManagerServer, RequestAcceptServiceLibrary, RequestGetStatus,
-    RequestServerStart, RequestServerStop, RequestWorkerStart, RequestWorkerStop,
+    CompressionEncoding, GridManager, GridManagerServer, RequestAcceptServiceLibrary,
+    RequestGetStatus, RequestServerStart, RequestServerStop, RequestWorkerStart, RequestWorkerStop,
     ResponseAcceptServiceLibrary, ResponseGetStatus, ResponseServerStart, ResponseServerStop,
     ResponseWorkerStart, ResponseWorkerStop, ServerConfiguration, ServerStatus,
     ServiceLibraryConfiguration, WorkerConfiguration, WorkerStatus,
 };
+use grid_server_interface::{compression_encoding_from_str, TlsPaths};
 use lazy_static::lazy_static;
 use std::env::{args, current_exe};
-use std::error::Error;
 use std::fs::{create_dir_all, File};
 use std::io::Write;
 use std::path::PathBuf;
@@ -51,17 +51,40 @@ const SERVICE_LIBRARY_NAME: &str = if cfg!(unix) {
     "service_library.dll"
 };
 
-///
+/// The number of jobs that the started grid workers hold at once, like the grid worker's default.
+/// It is passed in order to pass the compression after it.
+const WORKER_JOB_COUNT: u32 = 2;
+
+/// The options of the grid manager that are passed to the grid servers or grid workers it starts,
+/// without their "--server-" or "--worker-" prefix.
+const PASSED_OPTIONS: [&str; 4] = [
+    "role-secret-file",
+    "tls-ca-certificate",
+    "tls-certificate",
+    "tls-key",
+];
+
+/// The usage of the grid manager's command line.
+const USAGE: &str = "Usage: grid-manager <SOCKET_ADDRESS> [--compression gzip|zstd|none] \
+    [--server-role-secret-file <ROLE>=<PATH>]... [--server-tls-ca-certificate <PATH>] \
+    [--server-tls-certificate <PATH>] [--server-tls-key <PATH>] \
+    [--tls-ca-certificate <PATH>] [--tls-certificate <PATH>] [--tls-key <PATH>] \
+    [--worker-role-secret-file <PATH>] [--worker-tls-ca-certificate <PATH>] \
+    [--worker-tls-certificate <PATH>] [--worker-tls-key <PATH>]
+The options with the \"--server-\" and \"--worker-\" prefix are passed without it to the grid \
+servers and grid workers that the grid manager starts, and so is the compression.";
+
+/// Converts the given process ID into a `Pid`.
 fn pid_from_u64(process_id: u64) -> Pid {
     Pid::from(process_id as usize)
 }
 
-///
+/// Returns the ID of the given process.
 fn process_id_from_process(process: &Process) -> u64 {
     process.pid().as_u32() as u64
 }
 
-///
+/// Returns the path at which the library of the given service version is stored.
 fn service_library_path(service_id: u32, service_version: u32) -> PathBuf {
     let mut path = LIBRARIES_PATH.clone();
     path.push(service_id.to_string());
@@ -71,14 +94,30 @@ fn service_library_path(service_id: u32, service_version: u32) -> PathBuf {
     path
 }
 
-/// Starts a grid server process with the given configuration.
-fn start_server(server_configuration: &ServerConfiguration) {
+/// Returns the given option of the grid manager without the given prefix, if it is passed to the
+/// grid servers or grid workers with this prefix.
+fn passed_option(option: &str, prefix: &str) -> Option<String> {
+    option
+        .strip_prefix(prefix)
+        .filter(|passed_option| PASSED_OPTIONS.contains(passed_option))
+        .map(|passed_option| format!("--{passed_option}"))
+}
+
+/// Prints the usage of the grid manager and exits.
+fn print_usage_and_stop() -> ! {
+    error!("{USAGE}");
+    exit(-1);
+}
+
+/// Starts a grid server process with the given configuration and command line options.
+fn start_server(server_configuration: &ServerConfiguration, server_options: &[String]) {
     // Construct the grid server executable path.
     let grid_server_executable_path = GRID_BASE_PATH.clone().join(GRID_SERVER_EXECUTABLE_NAME);
 
     // Try to start the grid server according to the given configuration.
     if let Err(error) = Command::new(grid_server_executable_path)
         .args([server_configuration.server_address.clone()])
+        .args(server_options)
         .spawn()
     {
         error!("Could not start grid server: {error}");
@@ -87,8 +126,13 @@ fn start_server(server_configuration: &ServerConfiguration) {
     }
 }
 
-/// Starts a grid worker process with the given configuration.
-fn start_worker(worker_configuration: &WorkerConfiguration) {
+/// Starts a grid worker process with the given configuration, compression and command line
+/// options.
+fn start_worker(
+    worker_configuration: &WorkerConfiguration,
+    worker_compression: &str,
+    worker_options: &[String],
+) {
     // Construct the grid worker executable path.
     let grid_worker_executable_path = GRID_BASE_PATH.clone().join(GRID_WORKER_EXECUTABLE_NAME);
 
@@ -108,7 +152,10 @@ fn start_worker(worker_configuration: &WorkerConfiguration) {
                 .into_os_string()
                 .into_string()
                 .unwrap(),
+                WORKER_JOB_COUNT.to_string(),
+                worker_compression.to_string(),
             ])
+            .args(worker_options)
             .spawn()
         {
             error!("Could not start grid worker: {error}");
@@ -118,7 +165,7 @@ fn start_worker(worker_configuration: &WorkerConfiguration) {
     }
 }
 
-///
+/// Asks the process with the given ID to stop by sending it a Ctrl-C event.
 #[cfg(target_os = "windows")]
 fn stop_process_gracefully(server_pid: Pid) -> windows::core::Result<bool> {
     unsafe {
@@ -135,7 +182,8 @@ fn stop_process_gracefully(server_pid: Pid) -> windows::core::Result<bool> {
 #[derive(Debug)]
 struct KillError;
 
-///
+/// Asks the process with the given ID to stop by sending it `SIGINT`. Returns whether there is
+/// such a process.
 #[cfg(not(target_os = "windows"))]
 fn stop_process_gracefully(server_pid: Pid) -> Result<bool, KillError> {
     let mut system = SYSTEM.lock().unwrap();
@@ -154,12 +202,28 @@ fn stop_process_gracefully(server_pid: Pid) -> Result<bool, KillError> {
 // =================================================================================================
 
 /// The grid manager.
-pub struct GridManagerImpl {}
+pub struct GridManagerImpl {
+    /// The command line options of the grid servers it starts.
+    server_options: Vec<String>,
+    /// The compression of the grid workers it starts, "gzip", "zstd" or "none".
+    worker_compression: String,
+    /// The command line options of the grid workers it starts.
+    worker_options: Vec<String>,
+}
 
 impl GridManagerImpl {
-    ///
-    fn new() -> Self {
-        GridManagerImpl {}
+    /// Returns the grid manager, which starts grid servers and grid workers with the given
+    /// command line options and compression.
+    fn new(
+        server_options: Vec<String>,
+        worker_compression: String,
+        worker_options: Vec<String>,
+    ) -> Self {
+        GridManagerImpl {
+            server_options,
+            worker_compression,
+            worker_options,
+        }
     }
 }
 
@@ -249,13 +313,6 @@ impl GridManager for GridManagerImpl {
                     error_message: Some(error_message),
                 }));
             }
-
-            let error_message = "`accept_service_library()`: Unhandled".to_string();
-
-            error!("{}", &error_message);
-            return Ok(Response::new(ResponseAcceptServiceLibrary {
-                error_message: Some(error_message),
-            }));
         }
 
         let error_message = "No service library configuration given".to_string();
@@ -365,7 +422,7 @@ impl GridManager for GridManagerImpl {
 
         // A server configuration is given.
         if let Some(server_configuration) = &request.server_configuration {
-            start_server(server_configuration);
+            start_server(server_configuration, &self.server_options);
 
             return Ok(Response::new(ResponseServerStart {
                 error_message: None,
@@ -390,7 +447,11 @@ impl GridManager for GridManagerImpl {
 
         // A worker configuration is given.
         if let Some(worker_configuration) = &request.worker_configuration {
-            start_worker(worker_configuration);
+            start_worker(
+                worker_configuration,
+                &self.worker_compression,
+                &self.worker_options,
+            );
 
             return Ok(Response::new(ResponseWorkerStart {
                 error_message: None,
@@ -481,13 +542,64 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Construct the socket address from the command line argument,
     let socket_address = command_line_arguments[1].parse()?;
 
+    // Parse the options after the socket address.
+    let mut maybe_compression = None;
+    let mut tls_paths = TlsPaths::default();
+    let mut server_options = vec![];
+    let mut worker_compression = "none";
+    let mut worker_options = vec![];
+    let mut options = command_line_arguments[2..].iter();
+
+    while let Some(option) = options.next() {
+        match (option.as_str(), options.next().map(String::as_str)) {
+            ("--compression", Some(compression @ ("gzip" | "zstd" | "none"))) => {
+                maybe_compression = compression_encoding_from_str(compression)?;
+                server_options.extend(["--compression".to_string(), compression.to_string()]);
+                worker_compression = compression;
+            }
+            (option, Some(path)) if tls_paths.set(option, path).is_ok() => {}
+            (option, Some(value)) => {
+                if let Some(server_option) = passed_option(option, "--server-") {
+                    server_options.extend([server_option, value.to_string()]);
+                } else if let Some(worker_option) = passed_option(option, "--worker-") {
+                    worker_options.extend([worker_option, value.to_string()]);
+                } else {
+                    print_usage_and_stop();
+                }
+            }
+            _ => print_usage_and_stop(),
+        }
+    }
+
     info!("Running the grid manager on \"{}\" ...", socket_address);
 
-    Server::builder()
-        .add_service(
-            // Lift the 4 MB limit so that bigger service libraries can be uploaded.
-            GridManagerServer::new(GridManagerImpl::new()).max_decoding_message_size(usize::MAX),
-        )
+    // Lift the 4 MB limit so that bigger service libraries can be uploaded. Accept compressed
+    // requests of every encoding, e.g. service library uploads, and compress the responses to the
+    // clients that accept the configured encoding.
+    let mut grid_manager_server = GridManagerServer::new(GridManagerImpl::new(
+        server_options,
+        worker_compression.to_string(),
+        worker_options,
+    ))
+    .max_decoding_message_size(usize::MAX)
+    .accept_compressed(CompressionEncoding::Gzip)
+    .accept_compressed(CompressionEncoding::Zstd);
+
+    if let Some(compression) = maybe_compression {
+        grid_manager_server = grid_manager_server.send_compressed(compression);
+    }
+
+    let mut server = Server::builder();
+
+    // Secure the connections with TLS, if a certificate is given.
+    if let Some(server_tls_config) = tls_paths.server_tls_config()? {
+        info!("Using TLS");
+
+        server = server.tls_config(server_tls_config)?;
+    }
+
+    server
+        .add_service(grid_manager_server)
         .serve(socket_address)
         .await?;
 
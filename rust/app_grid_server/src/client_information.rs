@@ -1,10 +1,13 @@
 use chrono::{DateTime, Utc};
+use grid_server_interface::ClientRole;
 
-///
+/// What the grid server knows about a registered client.
 #[derive(Debug)]
 pub struct ClientInformation {
     pub client_description: String,
     pub host_id: String,
     pub last_access: DateTime<Utc>,
+    /// Which RPCs the client may call.
+    pub role: ClientRole,
     pub user_id: String,
 }
@@ -0,0 +1,413 @@
+use crate::fair_share::FairShare;
+use crate::limits::Limits;
+use grid_server_interface::{
+    client_role_from_str, compression_encoding_from_str, read_role_secret, ClientRole,
+    CompressionEncoding, ServiceId, ServiceVersionRequirement, TlsPaths,
+};
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// The usage of the grid server's command line.
+pub const USAGE: &str = "Usage: grid-server <SOCKET_ADDRESS> \
+    [--client-limit <LIMIT>=<VALUE>]... [--compression gzip|zstd|none] \
+    [--data-directory <PATH>] [--drain-timeout <SECONDS>] [--fair-share clients|users] \
+    [--finished-job-retention <COUNT>] [--lease-duration <SECONDS>] \
+    [--max-attempts [<SERVICE_ID>=]<COUNT>]... [--max-data-size <BYTES>] \
+    [--metrics-address <SOCKET_ADDRESS>] \
+    [--result-cache <SERVICE_ID>]... [--result-cache-size <BYTES>] [--result-cache-ttl <SECONDS>] \
+    --role-secret-file <ROLE>=<PATH>... \
+    [--service-alias <SERVICE_ID>/<ALIAS>=<SERVICE_VERSIONS>]... [--session-timeout <SECONDS>] \
+    [--tls-ca-certificate <PATH>] [--tls-certificate <PATH>] [--tls-key <PATH>] \
+    [--trace-file <PATH>] [--user-limit [<USER_ID>/]<LIMIT>=<VALUE>]... \
+    [--user-weight <USER_ID>=<WEIGHT>]...
+The server uses TLS if a certificate and key are given, and mutual TLS if also a CA certificate \
+is given.
+The roles are \"submitter\", \"worker\" and \"controller\". The worker and controller roles \
+require a secret. A client may take the submitter role without a secret unless it is given one.
+A job that failed the maximum attempts, which are 1 unless given, is moved to the dead-letter \
+queue, so failed jobs are not retried by default.
+The results of the jobs of the services with a result cache complete identical jobs submitted \
+later.
+The limits are \"in-flight-jobs\", \"queued-bytes\" and \"queued-jobs\". Jobs exceeding a \
+queue limit are rejected, jobs exceeding the in-flight limit stay queued.
+The service versions are a version like \"41\", \">=41\" or \"latest\".";
+
+/// The longest time the grid server waits for the results of the dispatched jobs and for the
+/// clients to fetch them before it exits, unless given on the command line.
+const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The number of finished jobs whose status is kept after their results were fetched, unless
+/// given on the command line.
+const DEFAULT_FINISHED_JOB_RETENTION: usize = 10_000;
+
+/// The time a grid worker has to send a job's result, unless given on the command line.
+const DEFAULT_LEASE_DURATION: Duration = Duration::from_secs(60);
+
+/// How often a job may fail on a grid worker before it is moved to the dead-letter queue, unless
+/// given on the command line. Failed jobs are not retried by default.
+const DEFAULT_MAX_ATTEMPTS: u32 = 1;
+
+/// The largest job or result data in bytes, unless given on the command line.
+const DEFAULT_MAX_DATA_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// The largest total size of the cached result data in bytes, unless given on the command line.
+const DEFAULT_RESULT_CACHE_SIZE: u64 = 256 * 1024 * 1024;
+
+/// The time after which a cached result expires, unless given on the command line.
+const DEFAULT_RESULT_CACHE_TIME_TO_LIVE: Duration = Duration::from_secs(60 * 60);
+
+/// The grid server configuration, as given on the command line.
+#[derive(Debug)]
+pub struct Configuration {
+    /// The limits of every client.
+    pub client_limits: Limits,
+    /// The compression of the responses to the clients that accept it. The requests of the
+    /// clients may be compressed either way. Not compressed if not given.
+    pub compression: Option<CompressionEncoding>,
+    /// The directory in which jobs and results are persisted. Nothing is persisted if not given.
+    pub data_directory: Option<PathBuf>,
+    /// The longest time the grid server waits for the results of the dispatched jobs and for the
+    /// clients to fetch them, once it was requested to stop.
+    pub drain_timeout: Duration,
+    /// Among whom the grid workers are shared fairly. Jobs with the same priority are dispatched
+    /// in the order of their submission if not given.
+    pub fair_share: Option<FairShare>,
+    /// The number of finished jobs whose state, labels and trace are kept after their results
+    /// were fetched or after they were cancelled, so that their status can still be asked for.
+    /// The jobs whose results were fetched first are forgotten first.
+    pub finished_job_retention: usize,
+    /// The time a grid worker has to send a job's result before the job is dispatched again.
+    pub lease_duration: Duration,
+    /// How often a job may fail on a grid worker before it is moved to the dead-letter queue,
+    /// unless given for its service. 1 by default, so that failed jobs are not retried. Expired
+    /// leases are not counted, they are always dispatched again.
+    pub max_attempts: u32,
+    /// How often a job may fail on a grid worker before it is moved to the dead-letter queue, per
+    /// service ID.
+    pub max_attempts_per_service_id: HashMap<ServiceId, u32>,
+    /// The largest job or result data in bytes, whether it is sent at once or in chunks.
+    pub max_data_size: u64,
+    /// The socket address on which the metrics are served over HTTP. Not served if not given.
+    pub metrics_socket_address: Option<SocketAddr>,
+    /// The IDs of the services whose results are cached, so that a job submitted with the same
+    /// service, service versions and job data as a succeeded job is completed with its result
+    /// without dispatching it. No results are cached if empty.
+    pub result_cache_service_ids: HashSet<ServiceId>,
+    /// The largest total size of the cached result data in bytes.
+    pub result_cache_size: u64,
+    /// The time after which a cached result expires.
+    pub result_cache_time_to_live: Duration,
+    /// The secret that a client has to present in order to register with a role, per role. The
+    /// worker and controller roles always have a secret, any client may register as a submitter if
+    /// it has none.
+    pub role_secret_per_role: HashMap<ClientRole, String>,
+    /// The service versions that jobs submitted for an alias require, per service ID and alias.
+    pub service_version_requirement_per_service_id_and_alias:
+        HashMap<(ServiceId, String), ServiceVersionRequirement>,
+    /// The time after which a client that did not access the grid server is dropped, once it has
+    /// neither jobs without a result nor results to fetch. Clients are never dropped if not
+    /// given.
+    pub session_timeout: Option<Duration>,
+    /// The socket address the grid server listens on.
+    pub socket_address: SocketAddr,
+    /// The certificate and key of the grid server for TLS, and the CA certificate that the
+    /// clients' certificates must be signed by for mutual TLS. Plaintext if not given.
+    pub tls_paths: TlsPaths,
+    /// The file to which the lifecycle of every job is appended as JSON lines. Jobs are not
+    /// traced to a file if not given.
+    pub trace_file_path: Option<PathBuf>,
+    /// The limits of every user, unless given for the user.
+    pub user_limits: Limits,
+    /// The limits per user ID, which override the limits of every user.
+    pub user_limits_per_user_id: HashMap<String, Limits>,
+    /// The weight of the users' shares, per user ID. The weight is 1 for other users.
+    pub weight_per_user_id: HashMap<String, u32>,
+}
+
+impl Configuration {
+    /// Parses the configuration from the given command line arguments, without the program name.
+    pub fn from_command_line_arguments(command_line_arguments: &[String]) -> Result<Self, String> {
+        let mut command_line_arguments = command_line_arguments.iter();
+
+        let socket_address = command_line_arguments
+            .next()
+            .ok_or_else(|| "Please pass the server socket address.".to_string())?
+            .parse()
+            .map_err(|error| format!("Invalid server socket address: {error}"))?;
+
+        let mut configuration = Configuration {
+            client_limits: Limits::default(),
+            compression: None,
+            data_directory: None,
+            drain_timeout: DEFAULT_DRAIN_TIMEOUT,
+            fair_share: None,
+            finished_job_retention: DEFAULT_FINISHED_JOB_RETENTION,
+            lease_duration: DEFAULT_LEASE_DURATION,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            max_attempts_per_service_id: HashMap::new(),
+            max_data_size: DEFAULT_MAX_DATA_SIZE,
+            metrics_socket_address: None,
+            result_cache_service_ids: HashSet::new(),
+            result_cache_size: DEFAULT_RESULT_CACHE_SIZE,
+            result_cache_time_to_live: DEFAULT_RESULT_CACHE_TIME_TO_LIVE,
+            role_secret_per_role: HashMap::new(),
+            service_version_requirement_per_service_id_and_alias: HashMap::new(),
+            session_timeout: None,
+            socket_address,
+            tls_paths: TlsPaths::default(),
+            trace_file_path: None,
+            user_limits: Limits::default(),
+            user_limits_per_user_id: HashMap::new(),
+            weight_per_user_id: HashMap::new(),
+        };
+
+        while let Some(option) = command_line_arguments.next() {
+            let mut value = || {
+                command_line_arguments
+                    .next()
+                    .ok_or_else(|| format!("Missing value for \"{option}\""))
+            };
+
+            match option.as_str() {
+                "--client-limit" => configuration.client_limits.set(value()?)?,
+                "--compression" => {
+                    configuration.compression = compression_encoding_from_str(value()?)?;
+                }
+                "--data-directory" => {
+                    configuration.data_directory = Some(PathBuf::from(value()?));
+                }
+                "--drain-timeout" => {
+                    let seconds: u64 = value()?
+                        .parse()
+                        .map_err(|error| format!("Invalid drain timeout: {error}"))?;
+
+                    configuration.drain_timeout = Duration::from_secs(seconds);
+                }
+                "--fair-share" => {
+                    configuration.fair_share = Some(value()?.parse()?);
+                }
+                "--finished-job-retention" => {
+                    configuration.finished_job_retention = value()?
+                        .parse()
+                        .map_err(|error| format!("Invalid finished job retention: {error}"))?;
+                }
+                "--lease-duration" => {
+                    let seconds: u64 = value()?
+                        .parse()
+                        .map_err(|error| format!("Invalid lease duration: {error}"))?;
+
+                    if seconds == 0 {
+                        return Err("The lease duration must not be 0".to_string());
+                    }
+
+                    configuration.lease_duration = Duration::from_secs(seconds);
+                }
+                "--max-attempts" => {
+                    let value = value()?;
+
+                    // Split the optional service ID from the count.
+                    let (maybe_service_id, count) = match value.split_once('=') {
+                        Some((service_id, count)) => (
+                            Some(service_id.parse::<ServiceId>().map_err(|error| {
+                                format!("Invalid service ID \"{service_id}\": {error}")
+                            })?),
+                            count,
+                        ),
+                        None => (None, value.as_str()),
+                    };
+
+                    let count: u32 = count
+                        .parse()
+                        .map_err(|error| format!("Invalid maximum attempts: {error}"))?;
+
+                    if count == 0 {
+                        return Err("The maximum attempts must not be 0".to_string());
+                    }
+
+                    match maybe_service_id {
+                        Some(service_id) => {
+                            configuration
+                                .max_attempts_per_service_id
+                                .insert(service_id, count);
+                        }
+                        None => configuration.max_attempts = count,
+                    }
+                }
+                "--max-data-size" => {
+                    configuration.max_data_size = value()?
+                        .parse()
+                        .map_err(|error| format!("Invalid maximum data size: {error}"))?;
+                }
+                "--metrics-address" => {
+                    configuration.metrics_socket_address = Some(
+                        value()?
+                            .parse()
+                            .map_err(|error| format!("Invalid metrics socket address: {error}"))?,
+                    );
+                }
+                "--result-cache" => {
+                    let service_id = value()?;
+
+                    configuration
+                        .result_cache_service_ids
+                        .insert(service_id.parse().map_err(|error| {
+                            format!("Invalid service ID \"{service_id}\": {error}")
+                        })?);
+                }
+                "--result-cache-size" => {
+                    configuration.result_cache_size = value()?
+                        .parse()
+                        .map_err(|error| format!("Invalid result cache size: {error}"))?;
+                }
+                "--result-cache-ttl" => {
+                    let seconds: u64 = value()?
+                        .parse()
+                        .map_err(|error| format!("Invalid result cache TTL: {error}"))?;
+
+                    configuration.result_cache_time_to_live = Duration::from_secs(seconds);
+                }
+                "--role-secret-file" => {
+                    let value = value()?;
+
+                    let (role, path) = value
+                        .split_once('=')
+                        .ok_or_else(|| format!("Invalid role secret file \"{value}\""))?;
+
+                    configuration
+                        .role_secret_per_role
+                        .insert(client_role_from_str(role)?, read_role_secret(path)?);
+                }
+                "--service-alias" => {
+                    let value = value()?;
+
+                    let (service_id_and_alias, service_version_requirement) = value
+                        .split_once('=')
+                        .ok_or_else(|| format!("Invalid service alias \"{value}\""))?;
+                    let (service_id, alias) = service_id_and_alias
+                        .split_once('/')
+                        .ok_or_else(|| format!("Invalid service alias \"{value}\""))?;
+
+                    let service_id: ServiceId = service_id
+                        .parse()
+                        .map_err(|error| format!("Invalid service ID \"{service_id}\": {error}"))?;
+                    let service_version_requirement: ServiceVersionRequirement =
+                        service_version_requirement.parse()?;
+
+                    // An alias must not refer to another alias.
+                    if let ServiceVersionRequirement::Alias(_) = service_version_requirement {
+                        return Err(format!(
+                            "Invalid service versions of alias \"{alias}\": \
+                             \"{service_version_requirement}\""
+                        ));
+                    }
+
+                    configuration
+                        .service_version_requirement_per_service_id_and_alias
+                        .insert((service_id, alias.to_string()), service_version_requirement);
+                }
+                "--session-timeout" => {
+                    let seconds: u64 = value()?
+                        .parse()
+                        .map_err(|error| format!("Invalid session timeout: {error}"))?;
+
+                    // A session timeout of 0 disables dropping clients.
+                    configuration.session_timeout = match seconds {
+                        0 => None,
+                        seconds => Some(Duration::from_secs(seconds)),
+                    };
+                }
+                "--tls-ca-certificate" | "--tls-certificate" | "--tls-key" => {
+                    configuration.tls_paths.set(option, value()?)?;
+                }
+                "--trace-file" => {
+                    configuration.trace_file_path = Some(PathBuf::from(value()?));
+                }
+                "--user-limit" => {
+                    let value = value()?;
+
+                    // Split the optional user ID from the limit.
+                    match value.split_once('/') {
+                        // The user IDs are sent in lower case by the clients.
+                        Some((user_id, limit)) => configuration
+                            .user_limits_per_user_id
+                            .entry(user_id.to_lowercase())
+                            .or_default()
+                            .set(limit)?,
+                        None => configuration.user_limits.set(value)?,
+                    }
+                }
+                "--user-weight" => {
+                    let value = value()?;
+
+                    let (user_id, weight) = value
+                        .split_once('=')
+                        .ok_or_else(|| format!("Invalid user weight \"{value}\""))?;
+
+                    let weight: u32 = weight.parse().map_err(|error| {
+                        format!("Invalid weight of user \"{user_id}\": {error}")
+                    })?;
+
+                    if weight == 0 {
+                        return Err("The user weight must not be 0".to_string());
+                    }
+
+                    // The user IDs are sent in lower case by the clients.
+                    configuration
+                        .weight_per_user_id
+                        .insert(user_id.to_lowercase(), weight);
+                }
+                _ => return Err(format!("Unknown option \"{option}\"")),
+            }
+        }
+
+        // Workers receive the job data and controllers may stop the grid server, so these roles
+        // are never granted to any client.
+        for (role, role_name) in [
+            (ClientRole::Worker, "worker"),
+            (ClientRole::Controller, "controller"),
+        ] {
+            if !configuration.role_secret_per_role.contains_key(&role) {
+                return Err(format!(
+                    "Missing secret for the role \"{role_name}\", see \"--role-secret-file\""
+                ));
+            }
+        }
+
+        Ok(configuration)
+    }
+
+    /// Returns the limits of the given user.
+    pub fn user_limits(&self, user_id: &str) -> Limits {
+        self.user_limits_per_user_id
+            .get(user_id)
+            .copied()
+            .unwrap_or_default()
+            .or(self.user_limits)
+    }
+
+    /// Returns whether the in-flight jobs of any client or user are limited.
+    pub fn limits_in_flight_jobs(&self) -> bool {
+        self.client_limits.max_in_flight_jobs.is_some()
+            || self.user_limits.max_in_flight_jobs.is_some()
+            || self
+                .user_limits_per_user_id
+                .values()
+                .any(|limits| limits.max_in_flight_jobs.is_some())
+    }
+
+    /// Returns the weight of the given user's share.
+    pub fn user_weight(&self, user_id: &str) -> u32 {
+        self.weight_per_user_id.get(user_id).copied().unwrap_or(1)
+    }
+
+    /// Returns how often a job of the given service may fail on a grid worker before it is moved to
+    /// the dead-letter queue.
+    pub fn max_attempts(&self, service_id: ServiceId) -> u32 {
+        self.max_attempts_per_service_id
+            .get(&service_id)
+            .copied()
+            .unwrap_or(self.max_attempts)
+    }
+}
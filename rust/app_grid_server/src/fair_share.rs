@@ -0,0 +1,64 @@
+use grid_server_interface::ClientId;
+use std::str::FromStr;
+
+/// Among whom the grid workers are shared fairly, for the jobs with the same priority.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FairShare {
+    /// Every client gets its share, weighted by the weight of its user.
+    Clients,
+    /// Every user gets its share, weighted by its weight.
+    Users,
+}
+
+impl FromStr for FairShare {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "clients" => Ok(FairShare::Clients),
+            "users" => Ok(FairShare::Users),
+            _ => Err(format!(
+                "Invalid fair share \"{string}\", expected \"clients\" or \"users\""
+            )),
+        }
+    }
+}
+
+/// Whom a queued job is accounted to.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum ShareKey {
+    /// Fair share is disabled, so all jobs are accounted to the same share.
+    #[default]
+    Everyone,
+    Client(ClientId),
+    User(String),
+}
+
+/// The share a queued job is accounted to, together with the weight of the share.
+#[derive(Clone, Debug)]
+pub struct Share {
+    pub key: ShareKey,
+    /// A share with twice the weight gets twice as many jobs dispatched. At least 1.
+    pub weight: u32,
+}
+
+impl Default for Share {
+    fn default() -> Self {
+        Share {
+            key: ShareKey::Everyone,
+            weight: 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fair_share_is_parsed() {
+        assert_eq!("clients".parse(), Ok(FairShare::Clients));
+        assert_eq!("users".parse(), Ok(FairShare::Users));
+        assert!("hosts".parse::<FairShare>().is_err());
+    }
+}
@@ -0,0 +1,378 @@
+use crate::fair_share::ShareKey;
+use crate::queued_job::QueuedJob;
+use grid_server_interface::{ClientId, Priority};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+/// The queued jobs of a service version. Jobs with a higher priority are dispatched first.
+///
+/// The jobs with the same priority are dispatched in the order in which they were queued, unless
+/// they are accounted to different shares. Then the share that got the fewest jobs dispatched
+/// relative to its weight is served next.
+#[derive(Debug, Default)]
+pub struct JobQueue {
+    /// The jobs per share per priority, starting with the highest priority. There are no empty
+    /// queues.
+    jobs_per_share_key_per_priority:
+        BTreeMap<Reverse<Priority>, BTreeMap<ShareKey, VecDeque<QueuedJob>>>,
+    /// The number of queued jobs and their job data bytes, per client ID.
+    queued_jobs_and_bytes_per_client_id: HashMap<ClientId, (u64, u64)>,
+    /// The virtual time of the last dispatched job.
+    virtual_time: f64,
+    /// The virtual time of every share with queued jobs. It advances by the inverse of the
+    /// share's weight with every dispatched job. A share that gets queued jobs starts at the
+    /// current virtual time, so that it can not catch up on the time it had no jobs.
+    virtual_time_per_share_key: HashMap<ShareKey, f64>,
+}
+
+impl JobQueue {
+    /// Queues the given job behind the other jobs with its priority and share.
+    pub fn push_back(&mut self, queued_job: QueuedJob) {
+        self.jobs_for(&queued_job).push_back(queued_job);
+    }
+
+    /// Queues the given job in front of the other jobs with its priority and share, e.g. to
+    /// retry it.
+    pub fn push_front(&mut self, queued_job: QueuedJob) {
+        self.jobs_for(&queued_job).push_front(queued_job);
+    }
+
+    /// Returns the job that is dispatched next among the jobs for which the given predicate
+    /// returns `true`, without removing it.
+    pub fn front_where(&self, predicate: impl Fn(&QueuedJob) -> bool) -> Option<&QueuedJob> {
+        let (priority, share_key, index) = self.next_position(predicate)?;
+
+        self.jobs_per_share_key_per_priority
+            .get(&priority)?
+            .get(&share_key)?
+            .get(index)
+    }
+
+    /// Removes the job that is dispatched next among the jobs for which the given predicate
+    /// returns `true`. The other jobs keep their places.
+    pub fn pop_front_where(&mut self, predicate: impl Fn(&QueuedJob) -> bool) -> Option<QueuedJob> {
+        let (priority, share_key, index) = self.next_position(predicate)?;
+        let jobs_per_share_key = self.jobs_per_share_key_per_priority.get_mut(&priority)?;
+
+        let jobs = jobs_per_share_key.get_mut(&share_key)?;
+        let queued_job = jobs.remove(index)?;
+
+        Self::count_removed_job(&mut self.queued_jobs_and_bytes_per_client_id, &queued_job);
+
+        if jobs.is_empty() {
+            jobs_per_share_key.remove(&share_key);
+        }
+
+        if jobs_per_share_key.is_empty() {
+            self.jobs_per_share_key_per_priority.remove(&priority);
+        }
+
+        // Advance the share's virtual time.
+        if let Some(virtual_time) = self.virtual_time_per_share_key.get_mut(&share_key) {
+            self.virtual_time = *virtual_time;
+            *virtual_time += 1.0 / f64::from(queued_job.share.weight.max(1));
+        }
+
+        self.remove_virtual_times_of_shares_without_jobs();
+
+        Some(queued_job)
+    }
+
+    /// Keeps only the jobs for which the given predicate returns `true`.
+    pub fn retain(&mut self, mut predicate: impl FnMut(&QueuedJob) -> bool) {
+        let queued_jobs_and_bytes_per_client_id = &mut self.queued_jobs_and_bytes_per_client_id;
+
+        self.jobs_per_share_key_per_priority
+            .retain(|_, jobs_per_share_key| {
+                jobs_per_share_key.retain(|_, jobs| {
+                    jobs.retain(|queued_job| {
+                        let retained = predicate(queued_job);
+
+                        if !retained {
+                            Self::count_removed_job(
+                                queued_jobs_and_bytes_per_client_id,
+                                queued_job,
+                            );
+                        }
+
+                        retained
+                    });
+                    !jobs.is_empty()
+                });
+                !jobs_per_share_key.is_empty()
+            });
+
+        self.remove_virtual_times_of_shares_without_jobs();
+    }
+
+    /// Returns the jobs, starting with the highest priority. The jobs with the same priority are
+    /// in the order in which they are dispatched, unless they are accounted to different shares.
+    pub fn iter(&self) -> impl Iterator<Item = &QueuedJob> {
+        self.jobs_per_share_key_per_priority
+            .values()
+            .flat_map(|jobs_per_share_key| jobs_per_share_key.values().flatten())
+    }
+
+    /// Returns the number of queued jobs of the given client and their job data bytes.
+    pub fn queued_jobs_and_bytes(&self, client_id: ClientId) -> (u64, u64) {
+        self.queued_jobs_and_bytes_per_client_id
+            .get(&client_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Counts the given job for its client, when it is queued.
+    fn count_queued_job(
+        queued_jobs_and_bytes_per_client_id: &mut HashMap<ClientId, (u64, u64)>,
+        queued_job: &QueuedJob,
+    ) {
+        let (queued_jobs, queued_bytes) = queued_jobs_and_bytes_per_client_id
+            .entry(queued_job.client_id)
+            .or_default();
+
+        *queued_jobs += 1;
+        *queued_bytes += queued_job.job.job_data.len() as u64;
+    }
+
+    /// Stops counting the given job for its client, when it is removed from the queue.
+    fn count_removed_job(
+        queued_jobs_and_bytes_per_client_id: &mut HashMap<ClientId, (u64, u64)>,
+        queued_job: &QueuedJob,
+    ) {
+        let Some((queued_jobs, queued_bytes)) =
+            queued_jobs_and_bytes_per_client_id.get_mut(&queued_job.client_id)
+        else {
+            return;
+        };
+
+        *queued_jobs -= 1;
+        *queued_bytes -= queued_job.job.job_data.len() as u64;
+
+        if *queued_jobs == 0 {
+            queued_jobs_and_bytes_per_client_id.remove(&queued_job.client_id);
+        }
+    }
+
+    /// Returns the queue for the priority and share of the given job, starting the share's
+    /// virtual time if it had no jobs before.
+    fn jobs_for(&mut self, queued_job: &QueuedJob) -> &mut VecDeque<QueuedJob> {
+        Self::count_queued_job(&mut self.queued_jobs_and_bytes_per_client_id, queued_job);
+
+        self.virtual_time_per_share_key
+            .entry(queued_job.share.key.clone())
+            .or_insert(self.virtual_time);
+
+        self.jobs_per_share_key_per_priority
+            .entry(Reverse(queued_job.priority))
+            .or_default()
+            .entry(queued_job.share.key.clone())
+            .or_default()
+    }
+
+    /// Returns the priority, the share key and the index within its share of the job that is
+    /// dispatched next among the jobs for which the given predicate returns `true`. That is the
+    /// first such job of the share with the highest priority and the earliest virtual time, or
+    /// with the oldest such job if there is a tie.
+    fn next_position(
+        &self,
+        predicate: impl Fn(&QueuedJob) -> bool,
+    ) -> Option<(Reverse<Priority>, ShareKey, usize)> {
+        self.jobs_per_share_key_per_priority
+            .iter()
+            .find_map(|(priority, jobs_per_share_key)| {
+                jobs_per_share_key
+                    .iter()
+                    .filter_map(|(share_key, jobs)| {
+                        let index = jobs.iter().position(&predicate)?;
+
+                        Some((share_key, index, &jobs[index]))
+                    })
+                    .min_by(
+                        |(share_key, _, queued_job), (other_share_key, _, other_queued_job)| {
+                            let virtual_time = self.virtual_time_per_share_key.get(*share_key);
+                            let other_virtual_time =
+                                self.virtual_time_per_share_key.get(*other_share_key);
+
+                            virtual_time
+                                .partial_cmp(&other_virtual_time)
+                                .unwrap_or(std::cmp::Ordering::Equal)
+                                .then(queued_job.job.job_id.cmp(&other_queued_job.job.job_id))
+                        },
+                    )
+                    .map(|(share_key, index, _)| (*priority, share_key.clone(), index))
+            })
+    }
+
+    /// Forgets the virtual times of the shares that have no queued jobs anymore.
+    fn remove_virtual_times_of_shares_without_jobs(&mut self) {
+        let jobs_per_share_key_per_priority = &self.jobs_per_share_key_per_priority;
+
+        self.virtual_time_per_share_key.retain(|share_key, _| {
+            jobs_per_share_key_per_priority
+                .values()
+                .any(|jobs_per_share_key| jobs_per_share_key.contains_key(share_key))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fair_share::Share;
+    use grid_server_interface::{Job, JobId};
+
+    /// Returns a queued job with the given ID, client and priority, accounted to everyone.
+    fn queued_job(job_id: JobId, client_id: ClientId, priority: Priority) -> QueuedJob {
+        QueuedJob::new(
+            client_id,
+            Job {
+                job_id,
+                job_data: vec![0; 10],
+                ..Default::default()
+            },
+            priority,
+            Share::default(),
+        )
+    }
+
+    /// Returns a queued job with the given ID and without priority, accounted to the given
+    /// client's share with the given weight.
+    fn shared_job(job_id: JobId, client_id: ClientId, weight: u32) -> QueuedJob {
+        QueuedJob {
+            share: Share {
+                key: ShareKey::Client(client_id),
+                weight,
+            },
+            ..queued_job(job_id, client_id, 0)
+        }
+    }
+
+    /// Removes every job and returns their IDs in the order in which they are dispatched.
+    fn dispatched_job_ids(job_queue: &mut JobQueue) -> Vec<JobId> {
+        std::iter::from_fn(|| job_queue.pop_front_where(|_| true))
+            .map(|queued_job| queued_job.job.job_id)
+            .collect()
+    }
+
+    #[test]
+    fn jobs_with_higher_priority_are_dispatched_first() {
+        let mut job_queue = JobQueue::default();
+
+        job_queue.push_back(queued_job(0, 1, 0));
+        job_queue.push_back(queued_job(1, 1, 5));
+        job_queue.push_back(queued_job(2, 1, -5));
+        job_queue.push_back(queued_job(3, 1, 5));
+        job_queue.push_back(queued_job(4, 1, 0));
+
+        assert_eq!(dispatched_job_ids(&mut job_queue), [1, 3, 0, 4, 2]);
+        assert!(job_queue.iter().next().is_none());
+    }
+
+    #[test]
+    fn retried_jobs_are_dispatched_before_the_other_jobs_with_their_priority() {
+        let mut job_queue = JobQueue::default();
+
+        job_queue.push_back(queued_job(0, 1, 0));
+        job_queue.push_back(queued_job(1, 1, 1));
+        job_queue.push_front(queued_job(2, 1, 0));
+
+        assert_eq!(
+            job_queue
+                .iter()
+                .map(|job| job.job.job_id)
+                .collect::<Vec<_>>(),
+            [1, 2, 0]
+        );
+        assert_eq!(dispatched_job_ids(&mut job_queue), [1, 2, 0]);
+    }
+
+    #[test]
+    fn jobs_that_may_not_be_dispatched_keep_their_places() {
+        let mut job_queue = JobQueue::default();
+
+        job_queue.push_back(queued_job(0, 1, 0));
+        job_queue.push_back(queued_job(1, 2, 0));
+        job_queue.push_back(queued_job(2, 1, 0));
+
+        let not_client_1 = |queued_job: &QueuedJob| queued_job.client_id != 1;
+
+        assert_eq!(job_queue.front_where(not_client_1).unwrap().job.job_id, 1);
+        assert_eq!(
+            job_queue.pop_front_where(not_client_1).unwrap().job.job_id,
+            1
+        );
+        assert!(job_queue.pop_front_where(not_client_1).is_none());
+        assert_eq!(dispatched_job_ids(&mut job_queue), [0, 2]);
+    }
+
+    #[test]
+    fn queued_jobs_and_bytes_are_counted_per_client() {
+        let mut job_queue = JobQueue::default();
+
+        job_queue.push_back(queued_job(0, 1, 0));
+        job_queue.push_back(queued_job(1, 1, 1));
+        job_queue.push_back(queued_job(2, 2, 0));
+
+        assert_eq!(job_queue.queued_jobs_and_bytes(1), (2, 20));
+        assert_eq!(job_queue.queued_jobs_and_bytes(2), (1, 10));
+
+        job_queue.retain(|queued_job| queued_job.job.job_id != 0);
+        assert_eq!(job_queue.queued_jobs_and_bytes(1), (1, 10));
+
+        job_queue.pop_front_where(|_| true);
+        assert_eq!(job_queue.queued_jobs_and_bytes(1), (0, 0));
+        assert_eq!(job_queue.queued_jobs_and_bytes(2), (1, 10));
+    }
+
+    #[test]
+    fn shares_get_jobs_dispatched_in_proportion_to_their_weights() {
+        let mut job_queue = JobQueue::default();
+
+        for job_id in 0..6 {
+            job_queue.push_back(shared_job(job_id, 1, 2));
+        }
+
+        for job_id in 6..12 {
+            job_queue.push_back(shared_job(job_id, 2, 1));
+        }
+
+        assert_eq!(
+            dispatched_job_ids(&mut job_queue),
+            [0, 6, 1, 2, 7, 3, 4, 8, 5, 9, 10, 11]
+        );
+    }
+
+    #[test]
+    fn shares_without_jobs_do_not_catch_up_later() {
+        let mut job_queue = JobQueue::default();
+
+        for job_id in 0..4 {
+            job_queue.push_back(shared_job(job_id, 1, 1));
+        }
+
+        assert_eq!(job_queue.pop_front_where(|_| true).unwrap().job.job_id, 0);
+        assert_eq!(job_queue.pop_front_where(|_| true).unwrap().job.job_id, 1);
+
+        // The new share starts at the current virtual time, instead of getting every job until
+        // it caught up with the other share.
+        job_queue.push_back(shared_job(10, 2, 1));
+        job_queue.push_back(shared_job(11, 2, 1));
+
+        assert_eq!(dispatched_job_ids(&mut job_queue), [10, 2, 11, 3]);
+    }
+
+    #[test]
+    fn priorities_come_before_shares() {
+        let mut job_queue = JobQueue::default();
+
+        job_queue.push_back(shared_job(0, 1, 1));
+        job_queue.push_back(shared_job(1, 1, 1));
+        job_queue.push_back(QueuedJob {
+            priority: 1,
+            ..shared_job(2, 1, 1)
+        });
+        job_queue.push_back(shared_job(3, 2, 100));
+
+        assert_eq!(dispatched_job_ids(&mut job_queue), [2, 3, 0, 1]);
+    }
+}
@@ -0,0 +1,73 @@
+use grid_server_interface::{ClientId, JobId};
+use serde_json::json;
+use std::fs::{File, OpenOptions};
+use std::io::{LineWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// A step in the lifecycle of a job.
+#[derive(Clone, Copy, Debug)]
+pub enum JobEvent {
+    /// The job was submitted by the given client.
+    Submitted { client_id: ClientId },
+    /// The job was dispatched to the given grid worker.
+    Dispatched { worker_client_id: ClientId },
+    /// The grid server received the job's final result.
+    ResultReceived,
+    /// The client fetched the job's result or it was streamed to the client.
+    ResultFetched,
+}
+
+/// A file to which every job event is appended as a line of JSON.
+pub struct TraceFile {
+    writer: Mutex<LineWriter<File>>,
+}
+
+impl TraceFile {
+    /// Opens the trace file at the given path, appending to it if it already exists.
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(TraceFile {
+            writer: Mutex::new(LineWriter::new(file)),
+        })
+    }
+
+    /// Appends the given event of the given jobs, which happened at the given time in RFC 3339
+    /// format.
+    pub fn write(&self, job_ids: &[JobId], job_event: JobEvent, time: &str) {
+        let mut writer = self.writer.lock().unwrap();
+
+        for job_id in job_ids {
+            let line = match job_event {
+                JobEvent::Submitted { client_id } => json!({
+                    "job_id": job_id,
+                    "event": "submitted",
+                    "time": time,
+                    "client_id": client_id,
+                }),
+                JobEvent::Dispatched { worker_client_id } => json!({
+                    "job_id": job_id,
+                    "event": "dispatched",
+                    "time": time,
+                    "worker_client_id": worker_client_id,
+                }),
+                JobEvent::ResultReceived => json!({
+                    "job_id": job_id,
+                    "event": "result_received",
+                    "time": time,
+                }),
+                JobEvent::ResultFetched => json!({
+                    "job_id": job_id,
+                    "event": "result_fetched",
+                    "time": time,
+                }),
+            };
+
+            if let Err(error) = writeln!(writer, "{line}") {
+                error!("Failed to write to the trace file: {error}");
+                return;
+            }
+        }
+    }
+}
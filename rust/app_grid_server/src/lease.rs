@@ -0,0 +1,21 @@
+use crate::fair_share::Share;
+use crate::service_versions::ServiceVersions;
+use grid_server_interface::{ClientId, Job, Priority, ServiceId, ServiceVersion};
+use std::time::Instant;
+
+/// A job that was dispatched to a grid worker, which has to send the result before the deadline.
+#[derive(Debug)]
+pub struct Lease {
+    pub client_id: ClientId,
+    pub deadline: Instant,
+    pub dispatched_at: Instant,
+    pub job: Job,
+    pub priority: Priority,
+    pub service_id: ServiceId,
+    /// The version of the grid worker's service.
+    pub service_version: ServiceVersion,
+    /// The versions of the service that may process the job, when it is queued again.
+    pub service_versions: ServiceVersions,
+    pub share: Share,
+    pub worker_client_id: ClientId,
+}
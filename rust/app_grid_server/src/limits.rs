@@ -0,0 +1,219 @@
+use grid_server_interface::ClientId;
+use std::collections::HashMap;
+
+/// What a client or user has queued in the grid server at a time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Usage {
+    /// The job data of the queued jobs, in bytes.
+    pub queued_bytes: u64,
+    /// The jobs waiting in a queue.
+    pub queued_jobs: u64,
+}
+
+/// The most a client or user may have in the grid server at a time. Nothing is limited that is
+/// not given.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Limits {
+    /// The most jobs that are dispatched at a time. Further jobs stay queued until a dispatched
+    /// job is finished.
+    pub max_in_flight_jobs: Option<u64>,
+    pub max_queued_bytes: Option<u64>,
+    pub max_queued_jobs: Option<u64>,
+}
+
+impl Limits {
+    /// Sets the limit from the given `<NAME>=<VALUE>` string, where the name is one of
+    /// "in-flight-jobs", "queued-bytes" and "queued-jobs".
+    pub fn set(&mut self, name_and_value: &str) -> Result<(), String> {
+        let (name, value) = name_and_value
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid limit \"{name_and_value}\""))?;
+
+        let value: u64 = value
+            .parse()
+            .map_err(|error| format!("Invalid value of limit \"{name}\": {error}"))?;
+
+        match name {
+            "in-flight-jobs" => self.max_in_flight_jobs = Some(value),
+            "queued-bytes" => self.max_queued_bytes = Some(value),
+            "queued-jobs" => self.max_queued_jobs = Some(value),
+            _ => {
+                return Err(format!(
+                    "Unknown limit \"{name}\", expected \"in-flight-jobs\", \"queued-bytes\" or \
+                     \"queued-jobs\""
+                ))
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns these limits, taking the limits that are not given from the given limits.
+    pub fn or(self, limits: Limits) -> Limits {
+        Limits {
+            max_in_flight_jobs: self.max_in_flight_jobs.or(limits.max_in_flight_jobs),
+            max_queued_bytes: self.max_queued_bytes.or(limits.max_queued_bytes),
+            max_queued_jobs: self.max_queued_jobs.or(limits.max_queued_jobs),
+        }
+    }
+
+    /// Checks whether the given usage plus the given number of new jobs with the given number of
+    /// bytes stays within the queue limits. Otherwise returns which limit of the given owner, e.g.
+    /// "client 3", would be exceeded.
+    pub fn check(
+        &self,
+        usage: &Usage,
+        job_count: u64,
+        byte_count: u64,
+        owner: &str,
+    ) -> Result<(), String> {
+        let limits_and_values = [
+            (
+                self.max_queued_jobs,
+                usage.queued_jobs,
+                job_count,
+                "queued jobs",
+            ),
+            (
+                self.max_queued_bytes,
+                usage.queued_bytes,
+                byte_count,
+                "queued bytes",
+            ),
+        ];
+
+        for (maybe_limit, value, increase, name) in limits_and_values {
+            if let Some(limit) = maybe_limit {
+                if value + increase > limit {
+                    return Err(format!(
+                        "The limit of {limit} {name} of {owner} would be exceeded: there are \
+                         {value} and {increase} were submitted"
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The number of dispatched jobs per client and per user, in order to dispatch only the jobs that
+/// stay within the in-flight limits.
+#[derive(Debug, Default)]
+pub struct InFlightJobs {
+    in_flight_jobs_per_client_id: HashMap<ClientId, u64>,
+    in_flight_jobs_per_user_id: HashMap<String, u64>,
+}
+
+impl InFlightJobs {
+    /// Counts a dispatched job of the given client and user.
+    pub fn add(&mut self, client_id: ClientId, user_id: &str) {
+        *self
+            .in_flight_jobs_per_client_id
+            .entry(client_id)
+            .or_default() += 1;
+        *self
+            .in_flight_jobs_per_user_id
+            .entry(user_id.to_string())
+            .or_default() += 1;
+    }
+
+    /// Returns whether another job of the given client and user may be dispatched, without
+    /// exceeding the given in-flight limits of the client and the user.
+    pub fn allow(
+        &self,
+        client_id: ClientId,
+        user_id: &str,
+        client_limits: &Limits,
+        user_limits: &Limits,
+    ) -> bool {
+        let within_limit = |maybe_limit: Option<u64>, in_flight_jobs: Option<&u64>| {
+            maybe_limit.is_none_or(|limit| in_flight_jobs.copied().unwrap_or_default() < limit)
+        };
+
+        within_limit(
+            client_limits.max_in_flight_jobs,
+            self.in_flight_jobs_per_client_id.get(&client_id),
+        ) && within_limit(
+            user_limits.max_in_flight_jobs,
+            self.in_flight_jobs_per_user_id.get(user_id),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queue_limits_reject_only_what_exceeds_them() {
+        let mut limits = Limits::default();
+        limits.set("queued-jobs=3").unwrap();
+        limits.set("queued-bytes=100").unwrap();
+
+        let usage = Usage {
+            queued_bytes: 90,
+            queued_jobs: 2,
+        };
+
+        assert!(limits.check(&usage, 1, 10, "client 1").is_ok());
+        assert!(limits
+            .check(&usage, 2, 0, "client 1")
+            .unwrap_err()
+            .contains("3 queued jobs of client 1"));
+        assert!(limits
+            .check(&usage, 1, 11, "client 1")
+            .unwrap_err()
+            .contains("100 queued bytes of client 1"));
+
+        // The in-flight limit does not reject submitted jobs.
+        limits.set("in-flight-jobs=0").unwrap();
+        assert!(limits.check(&usage, 1, 10, "client 1").is_ok());
+    }
+
+    #[test]
+    fn invalid_limits_are_rejected() {
+        let mut limits = Limits::default();
+
+        assert!(limits.set("queued-jobs").is_err());
+        assert!(limits.set("queued-jobs=many").is_err());
+        assert!(limits.set("running-jobs=1").is_err());
+    }
+
+    #[test]
+    fn missing_limits_are_taken_from_the_other_limits() {
+        let mut user_limits = Limits::default();
+        user_limits.set("queued-jobs=1").unwrap();
+        let mut default_limits = Limits::default();
+        default_limits.set("queued-jobs=2").unwrap();
+        default_limits.set("queued-bytes=3").unwrap();
+
+        let limits = user_limits.or(default_limits);
+
+        assert_eq!(limits.max_queued_jobs, Some(1));
+        assert_eq!(limits.max_queued_bytes, Some(3));
+        assert_eq!(limits.max_in_flight_jobs, None);
+    }
+
+    #[test]
+    fn in_flight_jobs_are_limited_per_client_and_per_user() {
+        let mut client_limits = Limits::default();
+        client_limits.set("in-flight-jobs=1").unwrap();
+        let mut user_limits = Limits::default();
+        user_limits.set("in-flight-jobs=2").unwrap();
+
+        let mut in_flight_jobs = InFlightJobs::default();
+        assert!(in_flight_jobs.allow(1, "user", &client_limits, &user_limits));
+
+        in_flight_jobs.add(1, "user");
+        assert!(!in_flight_jobs.allow(1, "user", &client_limits, &user_limits));
+        assert!(in_flight_jobs.allow(2, "user", &client_limits, &user_limits));
+
+        in_flight_jobs.add(2, "user");
+        assert!(!in_flight_jobs.allow(3, "user", &client_limits, &user_limits));
+        assert!(in_flight_jobs.allow(3, "other user", &client_limits, &user_limits));
+
+        // Nothing is limited that is not given.
+        assert!(in_flight_jobs.allow(1, "user", &Limits::default(), &Limits::default()));
+    }
+}
@@ -1,374 +1,4304 @@
+// The RPCs return `tonic::Status`, which is large, and so do the functions they call.
+#![allow(clippy::result_large_err)]
+
 mod client_information;
+mod configuration;
+mod fair_share;
+mod job_queue;
+mod job_trace;
+mod lease;
+mod limits;
+mod metrics;
+mod persistence;
+mod queued_job;
+mod result_cache;
+mod service_versions;
 
 #[macro_use]
 extern crate log;
 
 use crate::client_information::ClientInformation;
+use crate::configuration::{Configuration, USAGE};
+use crate::fair_share::{FairShare, Share, ShareKey};
+use crate::job_queue::JobQueue;
+use crate::job_trace::{JobEvent, TraceFile};
+use crate::lease::Lease;
+use crate::limits::{InFlightJobs, Usage};
+use crate::metrics::Metrics;
+use crate::persistence::{
+    record, ClientExpired, ClientRegistered, DeadLetterAdded, IdsReserved, JobCancelled,
+    JobDispatched, JobFinished, JobOutcome, JobSubmitted, Record, RecordKind, ResultAdded,
+    ResultsFetched, Store,
+};
+use crate::queued_job::QueuedJob;
+use crate::result_cache::{ResultCache, ResultCacheKey};
+use crate::service_versions::ServiceVersions;
 use chrono::Utc;
 use grid_server_interface::{
-    ClientId, GridServer, GridServerServer, Job, JobId, RequestFromClientJobSubmit,
-    RequestFromClientRegister, RequestFromClientResultFetch, RequestFromControllerStatusGet,
-    RequestFromWorkerExchange, RequestFromWorkerResultSubmit, ResponseToClientJobSubmit,
-    ResponseToClientRegister, ResponseToClientResultFetch, ResponseToControllerStatusGet,
-    ResponseToWorkerExchange, ResponseToWorkerResultSubmit, ServiceId, ServiceVersion,
+    labels_match, role_secrets_equal, split_into_chunks, ClientId, ClientRole, ClientStatus,
+    CompressionEncoding, DeadLetter, GridServer, GridServerServer, Job, JobDispatch, JobId,
+    JobQuery, JobState, JobStatus, JobTrace, Labels, Priority, QueueStatus,
+    RequestFromClientJobCancel, RequestFromClientJobStatusGet, RequestFromClientJobSubmit,
+    RequestFromClientJobTraceGet, RequestFromClientJobsSubmit, RequestFromClientRegister,
+    RequestFromClientResultAcknowledge, RequestFromClientResultFetch,
+    RequestFromClientResultStream, RequestFromControllerDeadLettersGet,
+    RequestFromControllerResultCacheFlush, RequestFromControllerServerDrain,
+    RequestFromControllerStatusGet, RequestFromWorkerExchange, RequestFromWorkerJobDownload,
+    RequestFromWorkerLeaseExtend, RequestFromWorkerResultSubmit, ResponseToClientJobCancel,
+    ResponseToClientJobStatusGet, ResponseToClientJobSubmit, ResponseToClientJobTraceGet,
+    ResponseToClientJobsSubmit, ResponseToClientRegister, ResponseToClientResultAcknowledge,
+    ResponseToClientResultFetch, ResponseToControllerDeadLettersGet,
+    ResponseToControllerResultCacheFlush, ResponseToControllerServerDrain,
+    ResponseToControllerStatusGet, ResponseToWorkerExchange, ResponseToWorkerLeaseExtend,
+    ResponseToWorkerResultSubmit, ResultStatus, ServiceId, ServiceVersion,
+    ServiceVersionRequirement, WorkerStatus, DATA_CHUNK_SIZE,
 };
+use hyper::header::CONTENT_TYPE;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, StatusCode};
 use lazy_static::lazy_static;
-use serde_json::json;
-use std::collections::{HashMap, VecDeque};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::convert::Infallible;
 use std::env::args;
+use std::net::SocketAddr;
+use std::pin::Pin;
 use std::process::exit;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use tonic::{transport::Server, Request, Response, Status};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::sync::Notify;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_stream::Stream;
+use tonic::{transport::Server, Request, Response, Status, Streaming};
 
 lazy_static! {
     static ref STOP_SERVER: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
 }
 
+/// The longest time a grid worker's request is held until a job is queued.
+const MAX_WAIT_DURATION: Duration = Duration::from_secs(60);
+
+/// The most jobs that are dispatched to a grid worker at once.
+const MAX_JOB_COUNT: u32 = 100;
+
+/// The sending end of a client's result stream.
+type ResultSender = UnboundedSender<Result<grid_server_interface::Result, Status>>;
+
 /// The grid server.
 pub struct GridServerImpl {
+    /// How often a job failed on a grid worker so far, per job ID.
+    attempts_per_job_id: Mutex<HashMap<JobId, u32>>,
+    /// The IDs of the cancelled jobs that a grid worker still has to be told about, per worker
+    /// client ID.
+    cancelled_job_ids_per_worker_client_id: Mutex<HashMap<ClientId, Vec<JobId>>>,
     /// A map from the job IDs to the ID of the client the job was submitted from.
     client_id_per_job_id: Mutex<HashMap<JobId, ClientId>>,
+    /// The ID of every registered client, per session token.
+    client_id_per_session_token: Mutex<HashMap<String, ClientId>>,
     /// Information for every client, per client ID.
     client_information_per_client_id: Mutex<HashMap<ClientId, ClientInformation>>,
-    /// A map from the client ID to a map of the service version to its jobs.
-    jobs_per_service_id_and_version:
-        Mutex<HashMap<ServiceId, HashMap<ServiceVersion, VecDeque<Job>>>>,
+    /// The configuration given on the command line.
+    configuration: Configuration,
+    /// The jobs that failed too often, per job ID.
+    dead_letters_per_job_id: Mutex<BTreeMap<JobId, DeadLetter>>,
+    /// The ID of the client and the final state of the finished jobs, per job ID, until they are
+    /// forgotten beyond the finished job retention.
+    finished_jobs_per_job_id: Mutex<HashMap<JobId, (ClientId, JobState)>>,
+    /// A map from the service ID to a map of the service versions that may process the jobs to
+    /// the jobs.
+    jobs_per_service_id_and_version: Mutex<HashMap<ServiceId, HashMap<ServiceVersions, JobQueue>>>,
+    /// Wakes the grid workers that wait for a job whenever a job is queued.
+    job_queued: Notify,
+    /// The ID of the client and the lifecycle of every job, per job ID, as long as the job's
+    /// status is known. The traces are not persisted.
+    job_traces_per_job_id: Mutex<HashMap<JobId, (ClientId, JobTrace)>>,
+    /// The ID of the client and the labels of every job with labels, per job ID, as long as the
+    /// job's status is known.
+    labels_per_job_id: Mutex<HashMap<JobId, (ClientId, Labels)>>,
+    /// The leases of the jobs that were dispatched to grid workers, per job ID.
+    leases_per_job_id: Mutex<HashMap<JobId, Lease>>,
+    /// The metrics, which are served to Prometheus if configured.
+    metrics: Metrics,
     /// The next client ID.
     next_client_id: Mutex<ClientId>,
     /// The next job ID.
     next_job_id: Mutex<JobId>,
+    /// The newest version that a grid worker asked jobs for, per service ID.
+    newest_service_version_per_service_id: Mutex<HashMap<ServiceId, ServiceVersion>>,
+    /// The results of the succeeded jobs of the services with a result cache.
+    result_cache: Mutex<ResultCache>,
+    /// The senders of the connected result streams, per client ID.
+    result_sender_per_client_id: Mutex<HashMap<ClientId, ResultSender>>,
     /// Results per client ID.
     results_per_client_id: Mutex<HashMap<ClientId, Vec<grid_server_interface::Result>>>,
+    /// The IDs of the finished jobs whose results were fetched and of the cancelled jobs, in that
+    /// order. Their state, labels and trace are kept for the finished job retention and forgotten
+    /// in this order.
+    retained_job_ids: Mutex<VecDeque<JobId>>,
+    /// The store in which jobs and results are persisted, if any.
+    store: Option<Store>,
+    /// Held while submitted jobs are checked against the limits and queued.
+    submission_lock: Mutex<()>,
+    /// The file to which every job event is written, if any.
+    trace_file: Option<TraceFile>,
+    /// The IDs of the grid workers that a job without a result was dispatched to, per job ID. Only
+    /// these may submit its result. They are not persisted.
+    worker_client_ids_per_job_id: Mutex<HashMap<JobId, HashSet<ClientId>>>,
 }
 
 impl GridServerImpl {
-    ///
-    fn new() -> Self {
+    /// Returns a grid server with the given configuration, store and trace file, which has no
+    /// clients and jobs yet.
+    fn new(
+        configuration: Configuration,
+        store: Option<Store>,
+        trace_file: Option<TraceFile>,
+    ) -> Self {
+        let result_cache = ResultCache::new(
+            configuration.result_cache_size,
+            configuration.result_cache_time_to_live,
+        );
+
         GridServerImpl {
+            attempts_per_job_id: Mutex::new(HashMap::new()),
+            cancelled_job_ids_per_worker_client_id: Mutex::new(HashMap::new()),
             client_id_per_job_id: Mutex::new(HashMap::new()),
+            client_id_per_session_token: Mutex::new(HashMap::new()),
             client_information_per_client_id: Mutex::new(HashMap::new()),
+            configuration,
+            dead_letters_per_job_id: Mutex::new(BTreeMap::new()),
+            finished_jobs_per_job_id: Mutex::new(HashMap::new()),
+            job_queued: Notify::new(),
+            job_traces_per_job_id: Mutex::new(HashMap::new()),
             jobs_per_service_id_and_version: Mutex::new(HashMap::new()),
+            labels_per_job_id: Mutex::new(HashMap::new()),
+            leases_per_job_id: Mutex::new(HashMap::new()),
+            metrics: Metrics::new(),
             next_client_id: Mutex::new(0),
             next_job_id: Mutex::new(0),
+            newest_service_version_per_service_id: Mutex::new(HashMap::new()),
+            result_cache: Mutex::new(result_cache),
+            result_sender_per_client_id: Mutex::new(HashMap::new()),
             results_per_client_id: Mutex::new(HashMap::new()),
+            retained_job_ids: Mutex::new(VecDeque::new()),
+            store,
+            submission_lock: Mutex::new(()),
+            trace_file,
+            worker_client_ids_per_job_id: Mutex::new(HashMap::new()),
         }
     }
 
+    /// Restores the clients, jobs and results from the given persisted records.
     ///
-    fn add_result(&self, result: &grid_server_interface::Result) {
-        let job_id = result.job_id;
-        let maybe_client_id_for_job_id = self.client_id_per_job_id.lock().unwrap().remove(&job_id);
+    /// Jobs that were dispatched to a worker but never got a result are queued again.
+    fn restore(&self, records: Vec<Record>) {
+        let mut jobs_per_service_id_and_version =
+            self.jobs_per_service_id_and_version.lock().unwrap();
+        let mut client_id_per_job_id = self.client_id_per_job_id.lock().unwrap();
+        let mut client_id_per_session_token = self.client_id_per_session_token.lock().unwrap();
+        let mut client_information_per_client_id =
+            self.client_information_per_client_id.lock().unwrap();
+        let mut dead_letters_per_job_id = self.dead_letters_per_job_id.lock().unwrap();
+        let mut finished_jobs_per_job_id = self.finished_jobs_per_job_id.lock().unwrap();
+        let mut labels_per_job_id = self.labels_per_job_id.lock().unwrap();
+        let mut next_client_id = self.next_client_id.lock().unwrap();
+        let mut next_job_id = self.next_job_id.lock().unwrap();
+        let mut results_per_client_id = self.results_per_client_id.lock().unwrap();
 
-        // There is a client ID for the given job ID.
-        if let Some(client_id_for_job_id) = maybe_client_id_for_job_id {
-            info!("Accepting result for job with ID {job_id} from client {client_id_for_job_id}");
+        // The jobs without a result, in the order of their submission.
+        let mut open_jobs: BTreeMap<JobId, (ClientId, ServiceId, ServiceVersions, Priority, Job)> =
+            BTreeMap::new();
+        let mut dispatched_job_ids = HashSet::new();
+        // The finished jobs whose results were fetched and the cancelled jobs, in that order.
+        let mut retained_job_ids = vec![];
 
-            // Collect the given result for the client ID.
-            self.results_per_client_id
-                .lock()
-                .unwrap()
-                .entry(client_id_for_job_id)
-                .or_default()
-                .push(result.clone());
-        }
-        // There is no client ID for the given job ID.
-        else {
-            warn!(
-                "`add_result()`: there is no client ID for the job ID {}.",
-                job_id
-            );
-        }
-    }
+        for record_kind in records.into_iter().filter_map(|record| record.record) {
+            match record_kind {
+                RecordKind::IdsReserved(ids_reserved) => {
+                    *next_client_id = (*next_client_id).max(ids_reserved.next_client_id);
+                    *next_job_id = (*next_job_id).max(ids_reserved.next_job_id);
+                }
+                RecordKind::ClientRegistered(client_registered) => {
+                    let client_id = client_registered.client_id;
+                    *next_client_id = (*next_client_id).max(client_id + 1);
 
-    ///
-    fn server_was_requested_to_stop(&self) -> bool {
-        // The server was requested to stop.
-        STOP_SERVER.load(Ordering::Relaxed)
-    }
+                    client_id_per_session_token.insert(client_registered.session_token, client_id);
+                    client_information_per_client_id.insert(
+                        client_id,
+                        ClientInformation {
+                            client_description: client_registered.client_description,
+                            host_id: client_registered.host_id,
+                            last_access: Utc::now(),
+                            role: ClientRole::try_from(client_registered.role).unwrap_or_default(),
+                            user_id: client_registered.user_id,
+                        },
+                    );
+                }
+                RecordKind::ClientExpired(client_expired) => {
+                    let client_id = client_expired.client_id;
 
-    ///
-    fn update_client_last_access_time(&self, _client_id: ClientId) {
-        let _client_information_per_client_id =
-            self.client_information_per_client_id.lock().unwrap();
+                    client_id_per_session_token
+                        .retain(|_, session_client_id| *session_client_id != client_id);
+                    client_information_per_client_id.remove(&client_id);
+                    results_per_client_id.remove(&client_id);
+                    finished_jobs_per_job_id
+                        .retain(|_, (finished_client_id, _)| *finished_client_id != client_id);
+                    labels_per_job_id.retain(|_, (job_client_id, _)| *job_client_id != client_id);
+                }
+                RecordKind::JobSubmitted(job_submitted) => {
+                    let job_id = job_submitted.job_id;
+                    *next_job_id = (*next_job_id).max(job_id + 1);
 
-        // TODO
-        /*
-        client_information_per_client_id
-            .entry(client_id)
-            .or_insert(ClientInformation {
-                client_id: format!("{client_id}"),
-                host_id: request.client_id.clone(),
-                last_access: Utc::now(),
-                user_id: request.user_id.clone(),
-            });
-         */
-    }
-}
+                    client_id_per_job_id.insert(job_id, job_submitted.client_id);
 
-/// The implementation of the server interface for the server.
-#[tonic::async_trait]
-impl GridServer for GridServerImpl {
-    async fn client_fetch_results(
-        &self,
-        request: Request<RequestFromClientResultFetch>,
-    ) -> Result<Response<ResponseToClientResultFetch>, Status> {
-        let request = request.get_ref();
-        let client_id = request.client_id;
+                    if !job_submitted.labels.is_empty() {
+                        labels_per_job_id.insert(
+                            job_id,
+                            (job_submitted.client_id, job_submitted.labels.clone()),
+                        );
+                    }
 
-        // Update the client's last access time.
-        self.update_client_last_access_time(client_id);
+                    open_jobs.insert(
+                        job_id,
+                        (
+                            job_submitted.client_id,
+                            job_submitted.service_id,
+                            ServiceVersions {
+                                oldest: job_submitted.service_version,
+                                newer: job_submitted.newer_service_versions,
+                            },
+                            job_submitted.priority,
+                            Job {
+                                job_data: job_submitted.job_data,
+                                job_id,
+                                job_data_size: 0,
+                                labels: job_submitted.labels,
+                            },
+                        ),
+                    );
+                }
+                RecordKind::JobDispatched(job_dispatched) => {
+                    dispatched_job_ids.insert(job_dispatched.job_id);
+                }
+                RecordKind::JobCancelled(job_cancelled) => {
+                    let job_id = job_cancelled.job_id;
 
-        let maybe_results = self
-            .results_per_client_id
-            .lock()
-            .unwrap()
-            .remove(&client_id);
+                    if let Some(client_id) = client_id_per_job_id.remove(&job_id) {
+                        finished_jobs_per_job_id.insert(job_id, (client_id, JobState::Cancelled));
+                        retained_job_ids.push(job_id);
+                    }
+                    open_jobs.remove(&job_id);
+                    dispatched_job_ids.remove(&job_id);
+                }
+                RecordKind::JobFinished(job_finished) => {
+                    finished_jobs_per_job_id.insert(
+                        job_finished.job_id,
+                        (
+                            job_finished.client_id,
+                            job_state_from_job_outcome(job_finished.outcome()),
+                        ),
+                    );
+                    retained_job_ids.push(job_finished.job_id);
 
-        // There are results for the given client ID.
-        if let Some(results) = maybe_results {
-            info!("Sending results to client {client_id}");
+                    if !job_finished.labels.is_empty() {
+                        labels_per_job_id.insert(
+                            job_finished.job_id,
+                            (job_finished.client_id, job_finished.labels),
+                        );
+                    }
+                }
+                RecordKind::ResultAdded(result_added) => {
+                    let job_id = result_added.job_id;
 
-            return Ok(Response::new(ResponseToClientResultFetch { results }));
-        }
+                    client_id_per_job_id.remove(&job_id);
+                    open_jobs.remove(&job_id);
+                    dispatched_job_ids.remove(&job_id);
 
-        Ok(Response::new(ResponseToClientResultFetch {
-            results: vec![],
-        }))
-    }
+                    finished_jobs_per_job_id.insert(
+                        job_id,
+                        (
+                            result_added.client_id,
+                            if result_added.failed {
+                                JobState::Failed
+                            } else {
+                                JobState::Completed
+                            },
+                        ),
+                    );
 
-    async fn client_register(
-        &self,
-        request: Request<RequestFromClientRegister>,
-    ) -> Result<Response<ResponseToClientRegister>, Status> {
-        // TODO: Grant or deny a client ID according to the request.
-        warn!("TODO: `client_register()`: grant or deny a client ID according to the request.");
+                    results_per_client_id
+                        .entry(result_added.client_id)
+                        .or_default()
+                        .push(grid_server_interface::Result {
+                            job_id,
+                            result_data: result_added.result_data,
+                            status: if result_added.failed {
+                                ResultStatus::Failed
+                            } else {
+                                ResultStatus::Succeeded
+                            }
+                            .into(),
+                            error_message: result_added.error_message,
+                            labels: labels_per_job_id
+                                .get(&job_id)
+                                .map(|(_, labels)| labels.clone())
+                                .unwrap_or_default(),
+                            result_data_size: 0,
+                        });
+                }
+                RecordKind::DeadLetterAdded(dead_letter_added) => {
+                    dead_letters_per_job_id.insert(
+                        dead_letter_added.job_id,
+                        DeadLetter {
+                            job: Some(Job {
+                                job_data: dead_letter_added.job_data,
+                                job_id: dead_letter_added.job_id,
+                                job_data_size: 0,
+                                labels: dead_letter_added.labels,
+                            }),
+                            client_id: dead_letter_added.client_id,
+                            service_id: dead_letter_added.service_id,
+                            service_version: dead_letter_added.service_version,
+                            attempts: dead_letter_added.attempts,
+                            error_message: dead_letter_added.error_message,
+                            failed_at: dead_letter_added.failed_at,
+                        },
+                    );
+                }
+                RecordKind::ResultsFetched(results_fetched) => {
+                    retained_job_ids.extend(&results_fetched.job_ids);
 
-        let mut next_client_id = self.next_client_id.lock().unwrap();
+                    let fetched_job_ids: HashSet<JobId> =
+                        results_fetched.job_ids.into_iter().collect();
 
-        // Get the current client ID.
-        let client_id = *next_client_id;
+                    if let Some(results) = results_per_client_id.get_mut(&results_fetched.client_id)
+                    {
+                        results.retain(|result| !fetched_job_ids.contains(&result.job_id));
 
-        // Save client information.
+                        if results.is_empty() {
+                            results_per_client_id.remove(&results_fetched.client_id);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Keep the finished jobs without a result to fetch for the finished job retention, in the
+        // order in which they got there.
         {
-            let request = request.get_ref();
-            let mut client_information_per_client_id =
-                self.client_information_per_client_id.lock().unwrap();
+            let job_ids_with_result: HashSet<JobId> = results_per_client_id
+                .values()
+                .flatten()
+                .map(|result| result.job_id)
+                .collect();
+            let mut known_job_ids = HashSet::new();
+            let mut retained_job_ids: VecDeque<JobId> = retained_job_ids
+                .into_iter()
+                .filter(|job_id| {
+                    finished_jobs_per_job_id.contains_key(job_id)
+                        && !job_ids_with_result.contains(job_id)
+                        && known_job_ids.insert(*job_id)
+                })
+                .collect();
+            let forgotten_job_count = retained_job_ids
+                .len()
+                .saturating_sub(self.configuration.finished_job_retention);
 
-            client_information_per_client_id
-                .entry(client_id)
-                .or_insert(ClientInformation {
-                    client_description: request.client_description.clone(),
-                    host_id: request.host_id.clone(),
-                    last_access: Utc::now(),
-                    user_id: request.user_id.clone(),
-                });
+            for job_id in retained_job_ids.drain(..forgotten_job_count) {
+                finished_jobs_per_job_id.remove(&job_id);
+                labels_per_job_id.remove(&job_id);
+            }
 
-            // client_information_per_client_id
+            *self.retained_job_ids.lock().unwrap() = retained_job_ids;
         }
 
-        // Increase the next client ID.
-        *next_client_id += 1;
+        info!(
+            "Restored {} clients, {} jobs ({} of them were dispatched before), {} results and {} \
+             dead letters",
+            client_information_per_client_id.len(),
+            open_jobs.len(),
+            dispatched_job_ids.len(),
+            results_per_client_id.values().map(Vec::len).sum::<usize>(),
+            dead_letters_per_job_id.len()
+        );
+
+        // Queue the jobs without a result again.
+        for (client_id, service_id, service_versions, priority, job) in open_jobs.into_values() {
+            let share = self.share_of_client(client_id, &client_information_per_client_id);
 
-        Ok(Response::new(ResponseToClientRegister { client_id }))
+            jobs_per_service_id_and_version
+                .entry(service_id)
+                .or_default()
+                .entry(service_versions)
+                .or_default()
+                .push_back(QueuedJob::new(client_id, job, priority, share));
+        }
     }
 
-    async fn client_submit_job(
-        &self,
-        request: Request<RequestFromClientJobSubmit>,
-    ) -> Result<Response<ResponseToClientJobSubmit>, Status> {
-        let request = request.get_ref();
-        let client_id = request.client_id;
+    /// Returns the records that describe the current state, in order to compact the store.
+    fn compacted_records(&self) -> Vec<Record> {
+        let mut records = vec![record(RecordKind::IdsReserved(IdsReserved {
+            next_client_id: *self.next_client_id.lock().unwrap(),
+            next_job_id: *self.next_job_id.lock().unwrap(),
+        }))];
 
-        // Update the client's last access time.
-        self.update_client_last_access_time(client_id);
+        // Add the clients.
+        {
+            let session_token_per_client_id: HashMap<ClientId, String> = self
+                .client_id_per_session_token
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(session_token, client_id)| (*client_id, session_token.clone()))
+                .collect();
+            let client_information_per_client_id =
+                self.client_information_per_client_id.lock().unwrap();
+            let mut client_ids: Vec<_> = client_information_per_client_id.keys().collect();
+            client_ids.sort();
 
-        // The server was requested to stop.
-        if self.server_was_requested_to_stop() {
-            info!("Server was requested to stop, rejecting new job from client");
+            for client_id in client_ids {
+                let client_information = &client_information_per_client_id[client_id];
 
-            // Do not accept any new jobs.
-            return Ok(Response::new(ResponseToClientJobSubmit { job_id: None }));
+                records.push(record(RecordKind::ClientRegistered(ClientRegistered {
+                    client_id: *client_id,
+                    client_description: client_information.client_description.clone(),
+                    host_id: client_information.host_id.clone(),
+                    user_id: client_information.user_id.clone(),
+                    session_token: session_token_per_client_id
+                        .get(client_id)
+                        .cloned()
+                        .unwrap_or_default(),
+                    role: client_information.role.into(),
+                })));
+            }
         }
 
-        let job_id;
+        // Add the queued jobs.
+        {
+            let jobs_per_service_id_and_version =
+                self.jobs_per_service_id_and_version.lock().unwrap();
+            let client_id_per_job_id = self.client_id_per_job_id.lock().unwrap();
+
+            for (service_id, jobs_per_service_version) in jobs_per_service_id_and_version.iter() {
+                for (service_versions, jobs) in jobs_per_service_version {
+                    for QueuedJob { job, priority, .. } in jobs.iter() {
+                        // Skip jobs that already got a result.
+                        if let Some(client_id) = client_id_per_job_id.get(&job.job_id) {
+                            records.push(record(RecordKind::JobSubmitted(JobSubmitted {
+                                job_id: job.job_id,
+                                client_id: *client_id,
+                                service_id: *service_id,
+                                service_version: service_versions.oldest,
+                                job_data: job.job_data.clone(),
+                                priority: *priority,
+                                newer_service_versions: service_versions.newer,
+                                labels: job.labels.clone(),
+                            })));
+                        }
+                    }
+                }
+            }
+        }
 
-        // Get the job ID.
+        // Add the finished jobs, those with a result to fetch first and then the retained ones in
+        // the order in which they are forgotten.
         {
-            // Get the next job ID.
-            let mut next_job_id = self.next_job_id.lock().unwrap();
+            let finished_jobs_per_job_id = self.finished_jobs_per_job_id.lock().unwrap();
+            let labels_per_job_id = self.labels_per_job_id.lock().unwrap();
+            let retained_job_ids = self.retained_job_ids.lock().unwrap();
 
-            // Copy the next job ID as the current job ID.
-            job_id = *next_job_id;
+            let known_retained_job_ids: HashSet<&JobId> = retained_job_ids.iter().collect();
+            let job_ids = finished_jobs_per_job_id
+                .keys()
+                .filter(|job_id| !known_retained_job_ids.contains(job_id))
+                .chain(retained_job_ids.iter());
 
-            // Increase the next job ID.
-            *next_job_id += 1;
+            for job_id in job_ids {
+                let Some((client_id, job_state)) = finished_jobs_per_job_id.get(job_id) else {
+                    continue;
+                };
+
+                records.push(record(RecordKind::JobFinished(JobFinished {
+                    job_id: *job_id,
+                    client_id: *client_id,
+                    outcome: job_outcome_from_job_state(*job_state).into(),
+                    labels: labels_per_job_id
+                        .get(job_id)
+                        .map(|(_, labels)| labels.clone())
+                        .unwrap_or_default(),
+                })));
+            }
         }
 
-        // Register the job ID with the given client ID.
-        self.client_id_per_job_id
-            .lock()
-            .unwrap()
-            .insert(job_id, client_id);
+        // Add the results that were not fetched yet.
+        for (client_id, results) in self.results_per_client_id.lock().unwrap().iter() {
+            for result in results {
+                records.push(record(RecordKind::ResultAdded(ResultAdded {
+                    client_id: *client_id,
+                    job_id: result.job_id,
+                    result_data: result.result_data.clone(),
+                    failed: result.status() == ResultStatus::Failed,
+                    error_message: result.error_message.clone(),
+                })));
+            }
+        }
 
-        info!("Accepting job with ID {job_id} from client {client_id}");
+        // Add the dead letters.
+        for dead_letter in self.dead_letters_per_job_id.lock().unwrap().values() {
+            records.push(record(RecordKind::DeadLetterAdded(
+                dead_letter_added_from_dead_letter(dead_letter),
+            )));
+        }
 
-        // Collect the job from the given request.
-        {
-            let mut jobs_per_service_id_and_version =
-                self.jobs_per_service_id_and_version.lock().unwrap();
+        records
+    }
 
-            // Add the given job data for the given service type and version.
-            jobs_per_service_id_and_version
-                .entry(request.service_id)
-                .or_default()
-                .entry(request.service_version)
-                .or_default()
-                .push_back(Job {
-                    job_data: request.job_data.clone(),
-                    job_id,
-                });
+    /// Appends the given records to the store, if there is one.
+    fn persist(&self, records: Vec<Record>) -> Result<(), Status> {
+        if let Some(store) = &self.store {
+            if let Err(error) = store.append(&records) {
+                error!("Could not persist to the store: {error}");
+                return Err(Status::internal(format!(
+                    "Could not persist to the store: {error}"
+                )));
+            }
         }
 
-        // Return the job ID.
-        Ok(Response::new(ResponseToClientJobSubmit {
-            job_id: Some(job_id),
-        }))
+        Ok(())
     }
 
-    async fn controller_get_status(
-        &self,
-        request: Request<RequestFromControllerStatusGet>,
-    ) -> Result<Response<ResponseToControllerStatusGet>, Status> {
-        let request = request.get_ref();
-        let client_id = request.client_id;
+    /// Adds the given result from the given grid worker. The result of a failed job is retried or
+    /// moved to the dead-letter queue, the result of a succeeded job is delivered to its client.
+    fn add_result(&self, worker_client_id: ClientId, result: &grid_server_interface::Result) {
+        let job_id = result.job_id;
 
-        // Update the client's last access time.
-        self.update_client_last_access_time(client_id);
+        // Only a worker that the job was dispatched to may submit its result.
+        let was_dispatched_to_worker = self
+            .worker_client_ids_per_job_id
+            .lock()
+            .unwrap()
+            .get(&job_id)
+            .is_some_and(|worker_client_ids| worker_client_ids.contains(&worker_client_id));
 
-        let mut status = HashMap::new();
+        if !was_dispatched_to_worker {
+            warn!(
+                "Ignoring the result of job with ID {job_id}, because it was not dispatched to \
+                 worker {worker_client_id} or already has a result"
+            );
+            return;
+        }
 
-        // Add the clients.
-        status.insert(
-            "clients".to_string(),
-            format!(
-                "{:?}",
-                self.client_information_per_client_id.lock().unwrap()
-            ),
-        );
-        // Add the queued jobs.
-        status.insert(
-            "jobs".to_string(),
-            format!("{:?}", self.jobs_per_service_id_and_version.lock().unwrap()),
-        );
-        // Add the queued results.
-        status.insert(
-            "results".to_string(),
-            format!("{:?}", self.results_per_client_id.lock().unwrap()),
-        );
+        // The job failed.
+        if result.status() == ResultStatus::Failed {
+            // Take the job's lease, unless it was passed on to another worker in the meantime.
+            let maybe_lease = {
+                let mut leases_per_job_id = self.leases_per_job_id.lock().unwrap();
 
-        Ok(Response::new(ResponseToControllerStatusGet {
-            status: json!(status).to_string(),
-        }))
-    }
+                match leases_per_job_id.get(&job_id) {
+                    Some(lease) if lease.worker_client_id == worker_client_id => {
+                        leases_per_job_id.remove(&job_id)
+                    }
+                    _ => None,
+                }
+            };
 
-    async fn worker_server_exchange(
-        &self,
-        request: Request<RequestFromWorkerExchange>,
-    ) -> Result<Response<ResponseToWorkerExchange>, Status> {
-        let request = request.get_ref();
-        let worker_client_id = request.client_id;
+            match maybe_lease {
+                Some(lease) => {
+                    self.metrics.job_processed(
+                        lease.service_id,
+                        lease.service_version,
+                        lease.dispatched_at.elapsed(),
+                    );
+                    self.retry_or_add_dead_letter(lease, &result.error_message)
+                }
+                None => warn!(
+                    "Ignoring the failure of job with ID {job_id}, because worker \
+                     {worker_client_id} does not hold its lease"
+                ),
+            }
 
-        // Update the grid worker client's last access time.
-        self.update_client_last_access_time(worker_client_id);
+            return;
+        }
 
-        {
-            // There is a result from the worker.
-            if let Some(result_from_worker) = &request.result_from_worker {
-                self.add_result(result_from_worker);
-            }
+        // The job's lease is fulfilled.
+        let maybe_lease = self.leases_per_job_id.lock().unwrap().remove(&job_id);
+        self.attempts_per_job_id.lock().unwrap().remove(&job_id);
+        self.worker_client_ids_per_job_id
+            .lock()
+            .unwrap()
+            .remove(&job_id);
 
-            // The server was requested to stop.
-            if self.server_was_requested_to_stop() {
-                info!("Server was requested to stop, not passing jobs to worker");
+        // Get the job's service from its lease, or remove the job from its queue if the lease
+        // expired before.
+        let maybe_service_id_and_version = match &maybe_lease {
+            Some(lease) => {
+                self.metrics.job_processed(
+                    lease.service_id,
+                    lease.service_version,
+                    lease.dispatched_at.elapsed(),
+                );
 
-                // Do not pass a jobs to workers.
-                return Ok(Response::new(ResponseToWorkerExchange { job: None }));
+                // Cache the result for identical jobs submitted later.
+                if self
+                    .configuration
+                    .result_cache_service_ids
+                    .contains(&lease.service_id)
+                {
+                    self.result_cache.lock().unwrap().insert(
+                        ResultCacheKey::new(
+                            lease.service_id,
+                            lease.service_versions,
+                            &lease.job.job_data,
+                        ),
+                        result.result_data.clone(),
+                    );
+                }
+
+                Some((lease.service_id, lease.service_version))
             }
+            None => self
+                .remove_queued_job(job_id)
+                .map(|(service_id, service_versions)| (service_id, service_versions.oldest)),
+        };
 
-            // Try to get jobs for the given request.
-            if let Some(query_job_from_server) = &request.query_job_from_server {
-                let mut jobs_per_service_id_and_version =
-                    self.jobs_per_service_id_and_version.lock().unwrap();
+        let maybe_client_id_for_job_id = self.client_id_per_job_id.lock().unwrap().remove(&job_id);
 
-                // There are jobs for the given service type.
-                if let Some(jobs_per_service_version) =
-                    jobs_per_service_id_and_version.get_mut(&query_job_from_server.service_id)
-                {
-                    // There are jobs for the given service version.
-                    if let Some(jobs) =
-                        jobs_per_service_version.get_mut(&query_job_from_server.service_version)
-                    {
-                        // Remove and return the first job.
-                        if let Some(job) = jobs.pop_front() {
-                            let job_id = job.job_id;
+        // There is a client ID for the given job ID.
+        if let Some(client_id_for_job_id) = maybe_client_id_for_job_id {
+            info!("Accepting result for job with ID {job_id} from client {client_id_for_job_id}");
 
-                            info!("Sending job with ID {job_id} to worker");
+            // Persist the result before it can be fetched. A failure is logged by `persist()`.
+            let _ = self.persist(vec![record(RecordKind::ResultAdded(ResultAdded {
+                client_id: client_id_for_job_id,
+                job_id,
+                result_data: result.result_data.clone(),
+                failed: false,
+                error_message: String::new(),
+            }))]);
 
-                            return Ok(Response::new(ResponseToWorkerExchange { job: Some(job) }));
-                        }
-                    }
-                }
+            self.finished_jobs_per_job_id
+                .lock()
+                .unwrap()
+                .insert(job_id, (client_id_for_job_id, JobState::Completed));
+
+            if let Some((service_id, service_version)) = maybe_service_id_and_version {
+                self.metrics.job_completed(service_id, service_version);
             }
-        }
 
-        Ok(Response::new(ResponseToWorkerExchange { job: None }))
+            self.deliver_result(client_id_for_job_id, result.clone());
+        }
+        // There is no client ID for the given job ID.
+        else {
+            warn!(
+                "`add_result()`: there is no client ID for the job ID {}.",
+                job_id
+            );
+        }
     }
 
-    async fn worker_submit_result(
-        &self,
-        request: Request<RequestFromWorkerResultSubmit>,
-    ) -> Result<Response<ResponseToWorkerResultSubmit>, Status> {
-        let request = request.get_ref();
-        let worker_client_id = request.client_id;
+    /// Queues the given leased job again after a failed attempt, or moves it to the dead-letter
+    /// queue if it was attempted too often.
+    fn retry_or_add_dead_letter(&self, lease: Lease, error_message: &str) {
+        let job_id = lease.job.job_id;
 
-        // Update the client's last access time.
-        self.update_client_last_access_time(worker_client_id);
+        // Count the failed attempt.
+        let attempts = {
+            let mut attempts_per_job_id = self.attempts_per_job_id.lock().unwrap();
+            let attempts = attempts_per_job_id.entry(job_id).or_default();
+            *attempts += 1;
+            *attempts
+        };
+        let max_attempts = self.configuration.max_attempts(lease.service_id);
 
-        // There is a result.
-        if let Some(result) = &request.result {
-            self.add_result(result);
+        // The job may be attempted again.
+        if attempts < max_attempts {
+            warn!(
+                "Job with ID {job_id} failed on worker {} (attempt {attempts} of {max_attempts}), \
+                 queueing it again: {error_message}",
+                lease.worker_client_id
+            );
+
+            self.requeue_job(lease);
+
+            return;
         }
 
-        Ok(Response::new(ResponseToWorkerResultSubmit {}))
-    }
-}
+        self.attempts_per_job_id.lock().unwrap().remove(&job_id);
+        self.worker_client_ids_per_job_id
+            .lock()
+            .unwrap()
+            .remove(&job_id);
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    env_logger::init();
+        let maybe_client_id = self.client_id_per_job_id.lock().unwrap().remove(&job_id);
 
-    // Get the given command line arguments.
-    let command_line_arguments: Vec<_> = args().collect();
+        // The job already got a result, e.g. from a worker whose lease expired before.
+        let Some(client_id) = maybe_client_id else {
+            return;
+        };
+
+        error!(
+            "Job with ID {job_id} failed {attempts} times, moving it to the dead-letter queue: \
+             {error_message}"
+        );
+
+        self.metrics
+            .job_failed(lease.service_id, lease.service_version);
+
+        let dead_letter = DeadLetter {
+            job: Some(lease.job),
+            client_id,
+            service_id: lease.service_id,
+            service_version: lease.service_version,
+            attempts,
+            error_message: error_message.to_string(),
+            failed_at: Utc::now().to_rfc3339(),
+        };
+        let failed_result = grid_server_interface::Result {
+            job_id,
+            result_data: vec![],
+            status: ResultStatus::Failed.into(),
+            error_message: error_message.to_string(),
+            labels: Labels::new(),
+            result_data_size: 0,
+        };
+
+        // Persist the dead letter and the failure. A failure to do so is logged by `persist()`.
+        let _ = self.persist(vec![
+            record(RecordKind::DeadLetterAdded(
+                dead_letter_added_from_dead_letter(&dead_letter),
+            )),
+            record(RecordKind::ResultAdded(ResultAdded {
+                client_id,
+                job_id,
+                result_data: vec![],
+                failed: true,
+                error_message: error_message.to_string(),
+            })),
+        ]);
+
+        self.dead_letters_per_job_id
+            .lock()
+            .unwrap()
+            .insert(job_id, dead_letter);
+        self.finished_jobs_per_job_id
+            .lock()
+            .unwrap()
+            .insert(job_id, (client_id, JobState::Failed));
 
-    // Too few command line arguments are given.
-    if command_line_arguments.len() < 2 {
-        error!("Please pass the server socket address.");
-        exit(-1);
+        // Let the client know that the job failed.
+        self.deliver_result(client_id, failed_result);
     }
 
-    // Construct the socket address from the command line argument,
-    let socket_address = command_line_arguments[1].parse()?;
+    /// Cancels the given jobs of the given client, which did not get a result yet, and returns
+    /// their IDs in ascending order.
+    ///
+    /// Queued jobs are removed and the workers processing dispatched jobs are told to abandon them.
+    fn cancel_jobs(&self, client_id: ClientId, job_ids: &[JobId]) -> Result<Vec<JobId>, Status> {
+        let cancelled_job_ids: HashSet<JobId> = {
+            let mut client_id_per_job_id = self.client_id_per_job_id.lock().unwrap();
 
-    info!("Running the server on \"{}\" ...", socket_address);
+            // Only jobs without a result, which were submitted by the client, can be cancelled.
+            let cancelled_job_ids: HashSet<JobId> = job_ids
+                .iter()
+                .filter(|job_id| client_id_per_job_id.get(job_id) == Some(&client_id))
+                .copied()
+                .collect();
 
-    // Register a signal handler.
-    let _ = signal_hook::flag::register(libc::SIGINT, Arc::clone(&STOP_SERVER));
+            // Persist the cancellation before it takes effect.
+            self.persist(
+                cancelled_job_ids
+                    .iter()
+                    .map(|job_id| {
+                        record(RecordKind::JobCancelled(JobCancelled { job_id: *job_id }))
+                    })
+                    .collect(),
+            )?;
 
-    Server::builder()
-        .add_service(GridServerServer::new(GridServerImpl::new()))
-        .serve(socket_address)
-        .await?;
+            let mut finished_jobs_per_job_id = self.finished_jobs_per_job_id.lock().unwrap();
 
-    Ok(())
+            for job_id in &cancelled_job_ids {
+                client_id_per_job_id.remove(job_id);
+                finished_jobs_per_job_id.insert(*job_id, (client_id, JobState::Cancelled));
+            }
+
+            cancelled_job_ids
+        };
+
+        if cancelled_job_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        {
+            let mut attempts_per_job_id = self.attempts_per_job_id.lock().unwrap();
+
+            let mut worker_client_ids_per_job_id =
+                self.worker_client_ids_per_job_id.lock().unwrap();
+
+            for job_id in &cancelled_job_ids {
+                attempts_per_job_id.remove(job_id);
+                worker_client_ids_per_job_id.remove(job_id);
+            }
+        }
+
+        // Remove the queued jobs.
+        for jobs_per_service_version in self
+            .jobs_per_service_id_and_version
+            .lock()
+            .unwrap()
+            .values_mut()
+        {
+            for jobs in jobs_per_service_version.values_mut() {
+                jobs.retain(|queued_job| !cancelled_job_ids.contains(&queued_job.job.job_id));
+            }
+        }
+
+        // Revoke the leases of the dispatched jobs, so that their workers are told to abandon them.
+        {
+            let mut leases_per_job_id = self.leases_per_job_id.lock().unwrap();
+            let mut cancelled_job_ids_per_worker_client_id =
+                self.cancelled_job_ids_per_worker_client_id.lock().unwrap();
+
+            for job_id in &cancelled_job_ids {
+                if let Some(lease) = leases_per_job_id.remove(job_id) {
+                    cancelled_job_ids_per_worker_client_id
+                        .entry(lease.worker_client_id)
+                        .or_default()
+                        .push(*job_id);
+                }
+            }
+        }
+
+        let mut cancelled_job_ids: Vec<JobId> = cancelled_job_ids.into_iter().collect();
+        cancelled_job_ids.sort();
+
+        self.retain_finished_jobs(&cancelled_job_ids);
+
+        Ok(cancelled_job_ids)
+    }
+
+    /// Returns the versions of the given service that the given requirement resolves to. The
+    /// latest version is resolved to the newest version that a grid worker asked jobs for so far,
+    /// or any newer one. It is an error while no grid worker asked for jobs of the service.
+    fn service_versions(
+        &self,
+        service_id: ServiceId,
+        service_version_requirement: ServiceVersionRequirement,
+    ) -> Result<ServiceVersions, Status> {
+        // Resolve an alias to the requirement it names.
+        let service_version_requirement = match service_version_requirement {
+            ServiceVersionRequirement::Alias(alias) => self
+                .configuration
+                .service_version_requirement_per_service_id_and_alias
+                .get(&(service_id, alias.clone()))
+                .cloned()
+                .ok_or_else(|| {
+                    Status::invalid_argument(format!(
+                        "Unknown alias \"{alias}\" of service {service_id}"
+                    ))
+                })?,
+            service_version_requirement => service_version_requirement,
+        };
+
+        match service_version_requirement {
+            ServiceVersionRequirement::Exactly(service_version) => {
+                Ok(ServiceVersions::exactly(service_version))
+            }
+            ServiceVersionRequirement::AtLeast(service_version) => {
+                Ok(ServiceVersions::at_least(service_version))
+            }
+            ServiceVersionRequirement::Latest => self
+                .newest_service_version_per_service_id
+                .lock()
+                .unwrap()
+                .get(&service_id)
+                .map(|service_version| ServiceVersions::at_least(*service_version))
+                .ok_or_else(|| {
+                    Status::failed_precondition(format!(
+                        "The latest version of service {service_id} is not known yet, because no \
+                         grid worker asked for its jobs"
+                    ))
+                }),
+            // Aliases do not name other aliases, see `Configuration`.
+            ServiceVersionRequirement::Alias(alias) => Err(Status::invalid_argument(format!(
+                "Alias \"{alias}\" of service {service_id} names another alias"
+            ))),
+        }
+    }
+
+    /// Queues the jobs of the given requests from the given client and returns their IDs, in order.
+    ///
+    /// The jobs are persisted with a single write and become visible to the grid workers together.
+    fn queue_jobs(
+        &self,
+        client_id: ClientId,
+        requests: Vec<RequestFromClientJobsSubmit>,
+    ) -> Result<Vec<JobId>, Status> {
+        let job_count = requests
+            .iter()
+            .map(|request| request.job_data.len() as JobId)
+            .sum::<JobId>();
+        let byte_count = requests
+            .iter()
+            .flat_map(|request| &request.job_data)
+            .map(|job_data| job_data.len() as u64)
+            .sum::<u64>();
+
+        // Get the service versions that may process the jobs of every request.
+        let mut service_versions_per_request = Vec::with_capacity(requests.len());
+
+        for request in &requests {
+            service_versions_per_request.push(self.service_versions(
+                request.service_id,
+                ServiceVersionRequirement::from_submission(
+                    request.service_version,
+                    request.service_version_constraint.as_ref(),
+                ),
+            )?);
+        }
+
+        // Reject the jobs if the data of one is too large or they exceed a limit of the client or
+        // its user.
+        for job_data in requests.iter().flat_map(|request| &request.job_data) {
+            self.check_data_size(job_data.len())?;
+        }
+
+        // Check the limits and queue the jobs under the same lock, so that jobs submitted at the
+        // same time can not exceed the limits together.
+        let submission_guard = self.submission_lock.lock().unwrap();
+
+        self.check_limits(client_id, job_count, byte_count)?;
+
+        // Reserve the job IDs.
+        let first_job_id = {
+            let mut next_job_id = self.next_job_id.lock().unwrap();
+            let first_job_id = *next_job_id;
+            *next_job_id += job_count;
+            first_job_id
+        };
+
+        // Assign the job IDs in the order of the requests.
+        let jobs: Vec<(ServiceId, ServiceVersions, Priority, Job)> = requests
+            .into_iter()
+            .zip(service_versions_per_request)
+            .flat_map(|(request, service_versions)| {
+                let service_id = request.service_id;
+                let priority = request.priority.unwrap_or_default();
+                let labels = request.labels;
+
+                request.job_data.into_iter().map(move |job_data| {
+                    (
+                        service_id,
+                        service_versions,
+                        priority,
+                        job_data,
+                        labels.clone(),
+                    )
+                })
+            })
+            .zip(first_job_id..)
+            .map(
+                |((service_id, service_versions, priority, job_data, labels), job_id)| {
+                    (
+                        service_id,
+                        service_versions,
+                        priority,
+                        Job {
+                            job_data,
+                            job_id,
+                            job_data_size: 0,
+                            labels,
+                        },
+                    )
+                },
+            )
+            .collect();
+
+        // Look up the results of the jobs of the services with a result cache. These jobs are
+        // completed right away instead of being queued.
+        let mut cached_result_data_per_job_id: HashMap<JobId, Vec<u8>> = HashMap::new();
+
+        if !self.configuration.result_cache_service_ids.is_empty() {
+            let mut result_cache = self.result_cache.lock().unwrap();
+
+            for (service_id, service_versions, _, job) in &jobs {
+                if !self
+                    .configuration
+                    .result_cache_service_ids
+                    .contains(service_id)
+                {
+                    continue;
+                }
+
+                if let Some(result_data) = result_cache.get(&ResultCacheKey::new(
+                    *service_id,
+                    *service_versions,
+                    &job.job_data,
+                )) {
+                    cached_result_data_per_job_id.insert(job.job_id, result_data);
+                }
+            }
+        }
+
+        // Persist the jobs, and the results of the jobs completed from the cache, before
+        // accepting them.
+        self.persist(
+            jobs.iter()
+                .map(|(service_id, service_versions, priority, job)| {
+                    record(RecordKind::JobSubmitted(JobSubmitted {
+                        job_id: job.job_id,
+                        client_id,
+                        service_id: *service_id,
+                        service_version: service_versions.oldest,
+                        job_data: job.job_data.clone(),
+                        priority: *priority,
+                        newer_service_versions: service_versions.newer,
+                        labels: job.labels.clone(),
+                    }))
+                })
+                .chain(
+                    cached_result_data_per_job_id
+                        .iter()
+                        .map(|(job_id, result_data)| {
+                            record(RecordKind::ResultAdded(ResultAdded {
+                                client_id,
+                                job_id: *job_id,
+                                result_data: result_data.clone(),
+                                failed: false,
+                                error_message: String::new(),
+                            }))
+                        }),
+                )
+                .collect(),
+        )?;
+
+        let job_ids: Vec<JobId> = jobs.iter().map(|(_, _, _, job)| job.job_id).collect();
+
+        // Count the submitted jobs per service.
+        {
+            let mut job_count_per_service_id_and_version: HashMap<
+                (ServiceId, ServiceVersions),
+                u64,
+            > = HashMap::new();
+
+            for (service_id, service_versions, _, _) in &jobs {
+                *job_count_per_service_id_and_version
+                    .entry((*service_id, *service_versions))
+                    .or_default() += 1;
+            }
+
+            for ((service_id, service_versions), job_count) in job_count_per_service_id_and_version
+            {
+                self.metrics
+                    .jobs_submitted(service_id, service_versions, job_count);
+            }
+        }
+
+        // Trace the jobs before a grid worker can get them.
+        self.trace_jobs(&job_ids, JobEvent::Submitted { client_id });
+
+        // Remember the labels of the jobs for filtering and for their results.
+        {
+            let mut labels_per_job_id = self.labels_per_job_id.lock().unwrap();
+
+            for (_, _, _, job) in jobs.iter().filter(|(_, _, _, job)| !job.labels.is_empty()) {
+                labels_per_job_id.insert(job.job_id, (client_id, job.labels.clone()));
+            }
+        }
+
+        let (cached_jobs, jobs): (Vec<_>, Vec<_>) = jobs
+            .into_iter()
+            .partition(|(_, _, _, job)| cached_result_data_per_job_id.contains_key(&job.job_id));
+
+        let share = self.share_of_client(
+            client_id,
+            &self.client_information_per_client_id.lock().unwrap(),
+        );
+
+        {
+            let mut jobs_per_service_id_and_version =
+                self.jobs_per_service_id_and_version.lock().unwrap();
+            let mut client_id_per_job_id = self.client_id_per_job_id.lock().unwrap();
+
+            for (service_id, service_versions, priority, job) in jobs {
+                // Register the job ID with the given client ID.
+                client_id_per_job_id.insert(job.job_id, client_id);
+
+                // Add the job for the given service type and versions.
+                jobs_per_service_id_and_version
+                    .entry(service_id)
+                    .or_default()
+                    .entry(service_versions)
+                    .or_default()
+                    .push_back(QueuedJob::new(client_id, job, priority, share.clone()));
+            }
+        }
+
+        drop(submission_guard);
+
+        match job_ids.as_slice() {
+            [job_id] => info!("Accepting job with ID {job_id} from client {client_id}"),
+            [first_job_id, .., last_job_id] => info!(
+                "Accepting jobs with IDs {first_job_id} to {last_job_id} from client {client_id}"
+            ),
+            [] => {}
+        }
+
+        // Wake the grid workers waiting for a job.
+        self.job_queued.notify_waiters();
+
+        // Complete the jobs with a cached result.
+        for (service_id, service_versions, _, job) in cached_jobs {
+            let job_id = job.job_id;
+
+            info!("Completing job with ID {job_id} of client {client_id} with a cached result");
+
+            self.finished_jobs_per_job_id
+                .lock()
+                .unwrap()
+                .insert(job_id, (client_id, JobState::Completed));
+            self.metrics
+                .job_completed(service_id, service_versions.oldest);
+
+            self.deliver_result(
+                client_id,
+                grid_server_interface::Result {
+                    job_id,
+                    result_data: cached_result_data_per_job_id
+                        .remove(&job_id)
+                        .unwrap_or_default(),
+                    status: ResultStatus::Succeeded.into(),
+                    error_message: String::new(),
+                    labels: Labels::new(),
+                    result_data_size: 0,
+                },
+            );
+        }
+
+        Ok(job_ids)
+    }
+
+    /// Returns the share that the jobs of the given client are accounted to.
+    fn share_of_client(
+        &self,
+        client_id: ClientId,
+        client_information_per_client_id: &HashMap<ClientId, ClientInformation>,
+    ) -> Share {
+        let user_id = client_information_per_client_id
+            .get(&client_id)
+            .map(|client_information| client_information.user_id.as_str())
+            .unwrap_or_default();
+
+        match self.configuration.fair_share {
+            None => Share::default(),
+            Some(FairShare::Clients) => Share {
+                key: ShareKey::Client(client_id),
+                weight: self.configuration.user_weight(user_id),
+            },
+            Some(FairShare::Users) => Share {
+                key: ShareKey::User(user_id.to_string()),
+                weight: self.configuration.user_weight(user_id),
+            },
+        }
+    }
+
+    /// Records the given event of the given jobs at the current time and writes it to the trace
+    /// file, if any.
+    fn trace_jobs(&self, job_ids: &[JobId], job_event: JobEvent) {
+        let now = Utc::now().to_rfc3339();
+
+        {
+            let mut job_traces_per_job_id = self.job_traces_per_job_id.lock().unwrap();
+
+            for job_id in job_ids {
+                // Start the trace when the job is submitted.
+                if let JobEvent::Submitted { client_id } = job_event {
+                    job_traces_per_job_id.insert(
+                        *job_id,
+                        (
+                            client_id,
+                            JobTrace {
+                                job_id: *job_id,
+                                submitted_at: now.clone(),
+                                ..Default::default()
+                            },
+                        ),
+                    );
+                    continue;
+                }
+
+                // Skip jobs that were restored without a trace or whose client expired.
+                let Some((_, job_trace)) = job_traces_per_job_id.get_mut(job_id) else {
+                    continue;
+                };
+
+                match job_event {
+                    JobEvent::Submitted { .. } => {}
+                    JobEvent::Dispatched { worker_client_id } => {
+                        job_trace.dispatches.push(JobDispatch {
+                            worker_client_id,
+                            dispatched_at: now.clone(),
+                        })
+                    }
+                    JobEvent::ResultReceived => job_trace.result_received_at = now.clone(),
+                    JobEvent::ResultFetched => job_trace.result_fetched_at = now.clone(),
+                }
+            }
+        }
+
+        if let Some(trace_file) = &self.trace_file {
+            trace_file.write(job_ids, job_event, &now);
+        }
+    }
+
+    /// Removes the queued job with the given ID and returns its service ID and the service versions
+    /// that may process it, if it is queued.
+    fn remove_queued_job(&self, job_id: JobId) -> Option<(ServiceId, ServiceVersions)> {
+        let mut jobs_per_service_id_and_version =
+            self.jobs_per_service_id_and_version.lock().unwrap();
+
+        for (service_id, jobs_per_service_version) in jobs_per_service_id_and_version.iter_mut() {
+            for (service_versions, jobs) in jobs_per_service_version.iter_mut() {
+                let mut removed = false;
+
+                jobs.retain(|queued_job| {
+                    removed |= queued_job.job.job_id == job_id;
+                    queued_job.job.job_id != job_id
+                });
+
+                if removed {
+                    return Some((*service_id, *service_versions));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns the number of queued jobs of the given clients and their job data bytes.
+    fn usage_of_clients(&self, client_ids: &HashSet<ClientId>) -> Usage {
+        let mut usage = Usage::default();
+
+        // Count the queued jobs.
+        for jobs_per_service_version in self
+            .jobs_per_service_id_and_version
+            .lock()
+            .unwrap()
+            .values()
+        {
+            for jobs in jobs_per_service_version.values() {
+                for client_id in client_ids {
+                    let (queued_jobs, queued_bytes) = jobs.queued_jobs_and_bytes(*client_id);
+
+                    usage.queued_jobs += queued_jobs;
+                    usage.queued_bytes += queued_bytes;
+                }
+            }
+        }
+
+        usage
+    }
+
+    /// Checks whether the given client may submit the given number of jobs with the given number
+    /// of bytes, without exceeding its queue limits or the queue limits of its user.
+    fn check_limits(
+        &self,
+        client_id: ClientId,
+        job_count: u64,
+        byte_count: u64,
+    ) -> Result<(), Status> {
+        // Get the user of the client and the user's clients.
+        let (user_id, client_ids_of_user): (String, HashSet<ClientId>) = {
+            let client_information_per_client_id =
+                self.client_information_per_client_id.lock().unwrap();
+
+            let user_id = client_information_per_client_id
+                .get(&client_id)
+                .map(|client_information| client_information.user_id.clone())
+                .unwrap_or_default();
+
+            let client_ids_of_user = client_information_per_client_id
+                .iter()
+                .filter(|(_, client_information)| client_information.user_id == user_id)
+                .map(|(client_id, _)| *client_id)
+                .collect();
+
+            (user_id, client_ids_of_user)
+        };
+
+        let client_limits = self.configuration.client_limits;
+        let user_limits = self.configuration.user_limits(&user_id);
+
+        client_limits
+            .check(
+                &self.usage_of_clients(&HashSet::from([client_id])),
+                job_count,
+                byte_count,
+                &format!("client {client_id}"),
+            )
+            .and_then(|()| {
+                user_limits.check(
+                    &self.usage_of_clients(&client_ids_of_user),
+                    job_count,
+                    byte_count,
+                    &format!("user \"{user_id}\""),
+                )
+            })
+            .map_err(|error_message| {
+                warn!("Rejecting jobs of client {client_id}: {error_message}");
+                Status::resource_exhausted(error_message)
+            })
+    }
+
+    /// Checks whether job or result data of the given size in bytes is allowed.
+    fn check_data_size(&self, data_size: usize) -> Result<(), Status> {
+        let max_data_size = self.configuration.max_data_size;
+
+        if data_size as u64 > max_data_size {
+            return Err(Status::resource_exhausted(format!(
+                "The data of {data_size} bytes exceeds the limit of {max_data_size} bytes"
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the metrics in the Prometheus text format, after updating the gauges.
+    fn metrics_text(&self) -> String {
+        let queue_depths: Vec<(ServiceId, ServiceVersions, usize)> = {
+            let jobs_per_service_id_and_version =
+                self.jobs_per_service_id_and_version.lock().unwrap();
+            let client_id_per_job_id = self.client_id_per_job_id.lock().unwrap();
+
+            jobs_per_service_id_and_version
+                .iter()
+                .flat_map(|(service_id, jobs_per_service_version)| {
+                    jobs_per_service_version
+                        .iter()
+                        .map(|(service_versions, jobs)| {
+                            (
+                                *service_id,
+                                *service_versions,
+                                // Skip jobs that got a result after their lease expired.
+                                jobs.iter()
+                                    .filter(|queued_job| {
+                                        client_id_per_job_id.contains_key(&queued_job.job.job_id)
+                                    })
+                                    .count(),
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect()
+        };
+        let pending_results = self
+            .results_per_client_id
+            .lock()
+            .unwrap()
+            .values()
+            .map(Vec::len)
+            .sum();
+        let registered_clients = self.client_information_per_client_id.lock().unwrap().len();
+
+        self.metrics
+            .set_gauges(&queue_depths, pending_results, registered_clients);
+
+        self.metrics.encode()
+    }
+
+    /// Removes up to the given number of queued jobs for the given query, skipping jobs that got
+    /// a result after their lease expired, leases them to the given worker and returns them.
+    ///
+    /// The jobs are taken from every queue whose service versions contain the queried version,
+    /// the jobs with the highest priority first. Jobs whose client or user has as many dispatched
+    /// jobs as its in-flight limit allows stay queued.
+    fn pop_jobs(
+        &self,
+        job_query: &JobQuery,
+        job_count: usize,
+        worker_client_id: ClientId,
+    ) -> Vec<QueuedJob> {
+        // Get the users of the clients, if the in-flight jobs are limited.
+        let maybe_user_id_per_client_id: Option<HashMap<ClientId, String>> =
+            self.configuration.limits_in_flight_jobs().then(|| {
+                self.client_information_per_client_id
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|(client_id, client_information)| {
+                        (*client_id, client_information.user_id.clone())
+                    })
+                    .collect()
+            });
+
+        let mut jobs_per_service_id_and_version =
+            self.jobs_per_service_id_and_version.lock().unwrap();
+
+        // There are jobs for the given service type.
+        let Some(jobs_per_service_version) =
+            jobs_per_service_id_and_version.get_mut(&job_query.service_id)
+        else {
+            return vec![];
+        };
+
+        let client_id_per_job_id = self.client_id_per_job_id.lock().unwrap();
+        // The leases are locked together with the queues, so that the jobs dispatched at the same
+        // time can not exceed the in-flight limits together.
+        let mut leases_per_job_id = self.leases_per_job_id.lock().unwrap();
+        let mut popped_jobs = vec![];
+
+        // Count the dispatched jobs of every client and user.
+        let mut in_flight_jobs = InFlightJobs::default();
+
+        if let Some(user_id_per_client_id) = &maybe_user_id_per_client_id {
+            for lease in leases_per_job_id.values() {
+                in_flight_jobs.add(
+                    lease.client_id,
+                    user_id_per_client_id
+                        .get(&lease.client_id)
+                        .map_or("", String::as_str),
+                );
+            }
+        }
+
+        while popped_jobs.len() < job_count {
+            // Only jobs within the in-flight limits of their client and user may be dispatched.
+            let may_dispatch = |queued_job: &QueuedJob| {
+                let Some(user_id_per_client_id) = &maybe_user_id_per_client_id else {
+                    return true;
+                };
+                let user_id = user_id_per_client_id
+                    .get(&queued_job.client_id)
+                    .map_or("", String::as_str);
+
+                in_flight_jobs.allow(
+                    queued_job.client_id,
+                    user_id,
+                    &self.configuration.client_limits,
+                    &self.configuration.user_limits(user_id),
+                )
+            };
+
+            // Find the queue with the next job for the given service version, preferring the
+            // higher priority and then the older job.
+            let maybe_service_versions_and_jobs = jobs_per_service_version
+                .iter_mut()
+                .filter(|(service_versions, _)| {
+                    service_versions.contains(job_query.service_version)
+                })
+                .filter_map(|(service_versions, jobs)| {
+                    let queued_job = jobs.front_where(may_dispatch)?;
+                    let order = (queued_job.priority, Reverse(queued_job.job.job_id));
+
+                    Some((order, service_versions, jobs))
+                })
+                .max_by_key(|(order, _, _)| *order)
+                .map(|(_, service_versions, jobs)| (*service_versions, jobs));
+
+            let Some((service_versions, jobs)) = maybe_service_versions_and_jobs else {
+                break;
+            };
+            let Some(queued_job) = jobs.pop_front_where(may_dispatch) else {
+                break;
+            };
+
+            if !client_id_per_job_id.contains_key(&queued_job.job.job_id) {
+                continue;
+            }
+
+            if let Some(user_id_per_client_id) = &maybe_user_id_per_client_id {
+                in_flight_jobs.add(
+                    queued_job.client_id,
+                    user_id_per_client_id
+                        .get(&queued_job.client_id)
+                        .map_or("", String::as_str),
+                );
+            }
+
+            // Lease the job to the worker.
+            leases_per_job_id.insert(
+                queued_job.job.job_id,
+                Lease {
+                    client_id: queued_job.client_id,
+                    deadline: Instant::now() + self.configuration.lease_duration,
+                    dispatched_at: Instant::now(),
+                    job: queued_job.job.clone(),
+                    priority: queued_job.priority,
+                    service_id: job_query.service_id,
+                    service_version: job_query.service_version,
+                    service_versions,
+                    share: queued_job.share.clone(),
+                    worker_client_id,
+                },
+            );
+
+            popped_jobs.push(queued_job);
+        }
+
+        popped_jobs
+    }
+
+    /// Removes the results of the given client that match the given predicate and returns them.
+    /// They are persisted and traced as fetched.
+    fn take_results(
+        &self,
+        client_id: ClientId,
+        predicate: impl Fn(&grid_server_interface::Result) -> bool,
+    ) -> Vec<grid_server_interface::Result> {
+        let results = {
+            let mut results_per_client_id = self.results_per_client_id.lock().unwrap();
+
+            let Some(results) = results_per_client_id.remove(&client_id) else {
+                return vec![];
+            };
+
+            // Keep the other results for later.
+            let (results, other_results): (Vec<_>, Vec<_>) =
+                results.into_iter().partition(|result| predicate(result));
+
+            if !other_results.is_empty() {
+                results_per_client_id.insert(client_id, other_results);
+            }
+
+            results
+        };
+
+        if !results.is_empty() {
+            let job_ids: Vec<JobId> = results.iter().map(|result| result.job_id).collect();
+
+            // Persist that the results were fetched. A failure is logged by `persist()`.
+            let _ = self.persist(vec![record(RecordKind::ResultsFetched(ResultsFetched {
+                client_id,
+                job_ids: job_ids.clone(),
+            }))]);
+
+            self.trace_jobs(&job_ids, JobEvent::ResultFetched);
+            self.retain_finished_jobs(&job_ids);
+        }
+
+        results
+    }
+
+    /// Keeps the state, labels and trace of the given finished jobs, whose results were fetched or
+    /// which were cancelled, for the finished job retention. Those of the jobs retained before are
+    /// forgotten once there are more than that.
+    fn retain_finished_jobs(&self, job_ids: &[JobId]) {
+        let forgotten_job_ids: Vec<JobId> = {
+            let mut retained_job_ids = self.retained_job_ids.lock().unwrap();
+
+            retained_job_ids.extend(job_ids);
+
+            let forgotten_job_count = retained_job_ids
+                .len()
+                .saturating_sub(self.configuration.finished_job_retention);
+
+            retained_job_ids.drain(..forgotten_job_count).collect()
+        };
+
+        if forgotten_job_ids.is_empty() {
+            return;
+        }
+
+        {
+            let mut finished_jobs_per_job_id = self.finished_jobs_per_job_id.lock().unwrap();
+            let mut labels_per_job_id = self.labels_per_job_id.lock().unwrap();
+
+            for job_id in &forgotten_job_ids {
+                finished_jobs_per_job_id.remove(job_id);
+                labels_per_job_id.remove(job_id);
+            }
+        }
+
+        {
+            let mut attempts_per_job_id = self.attempts_per_job_id.lock().unwrap();
+            let mut job_traces_per_job_id = self.job_traces_per_job_id.lock().unwrap();
+
+            for job_id in &forgotten_job_ids {
+                attempts_per_job_id.remove(job_id);
+                job_traces_per_job_id.remove(job_id);
+            }
+        }
+    }
+
+    /// Sends the given result to the client's result stream, if it is connected. Otherwise the
+    /// result is collected until the client fetches it or connects its result stream.
+    fn deliver_result(&self, client_id: ClientId, mut result: grid_server_interface::Result) {
+        // Echo the job's labels, whatever the grid worker sent.
+        result.labels = self
+            .labels_per_job_id
+            .lock()
+            .unwrap()
+            .get(&result.job_id)
+            .map(|(_, labels)| labels.clone())
+            .unwrap_or_default();
+
+        let mut results_per_client_id = self.results_per_client_id.lock().unwrap();
+        let mut result_sender_per_client_id = self.result_sender_per_client_id.lock().unwrap();
+        let job_id = result.job_id;
+
+        self.trace_jobs(&[job_id], JobEvent::ResultReceived);
+
+        // The client's result stream is connected. The chunks are sent while the senders are locked,
+        // so that the chunks of different results do not interleave.
+        let result = match result_sender_per_client_id.get(&client_id) {
+            Some(result_sender) => match split_result_into_chunks(result.clone())
+                .into_iter()
+                .try_for_each(|chunk| result_sender.send(Ok(chunk)))
+            {
+                Ok(()) => {
+                    info!("Streaming result for job with ID {job_id} to client {client_id}");
+
+                    drop(result_sender_per_client_id);
+                    drop(results_per_client_id);
+
+                    // Persist that the result was fetched, without holding the results. A failure
+                    // is logged by `persist()`.
+                    let _ =
+                        self.persist(vec![record(RecordKind::ResultsFetched(ResultsFetched {
+                            client_id,
+                            job_ids: vec![job_id],
+                        }))]);
+
+                    self.trace_jobs(&[job_id], JobEvent::ResultFetched);
+                    self.retain_finished_jobs(&[job_id]);
+
+                    return;
+                }
+                // The client disconnected in the meantime.
+                Err(_) => {
+                    result_sender_per_client_id.remove(&client_id);
+                    result
+                }
+            },
+            None => result,
+        };
+
+        // Collect the given result for the client ID.
+        results_per_client_id
+            .entry(client_id)
+            .or_default()
+            .push(result);
+    }
+
+    /// Queues the given leased job in front of the other jobs with its service and service
+    /// versions.
+    fn requeue_job(&self, lease: Lease) {
+        self.jobs_per_service_id_and_version
+            .lock()
+            .unwrap()
+            .entry(lease.service_id)
+            .or_default()
+            .entry(lease.service_versions)
+            .or_default()
+            .push_front(QueuedJob::new(
+                lease.client_id,
+                lease.job,
+                lease.priority,
+                lease.share,
+            ));
+
+        self.job_queued.notify_waiters();
+    }
+
+    /// Queues the jobs again whose lease expired without a result, e.g. because the grid worker
+    /// crashed or was stopped. An expired lease does not count as a failed attempt, since the job
+    /// may have been fine.
+    fn requeue_jobs_with_expired_lease(&self) {
+        let now = Instant::now();
+
+        // Remove the expired leases.
+        let mut expired_leases: Vec<Lease> = {
+            let mut leases_per_job_id = self.leases_per_job_id.lock().unwrap();
+
+            let expired_job_ids: Vec<JobId> = leases_per_job_id
+                .iter()
+                .filter(|(_, lease)| lease.deadline <= now)
+                .map(|(job_id, _)| *job_id)
+                .collect();
+
+            expired_job_ids
+                .iter()
+                .filter_map(|job_id| leases_per_job_id.remove(job_id))
+                .collect()
+        };
+
+        if expired_leases.is_empty() {
+            return;
+        }
+
+        // Queue the jobs in front of the other jobs, keeping the order of their submission.
+        expired_leases.sort_by_key(|lease| std::cmp::Reverse(lease.job.job_id));
+
+        for lease in expired_leases {
+            warn!(
+                "The lease of job with ID {} on worker {} expired without a result, queueing the \
+                 job again",
+                lease.job.job_id, lease.worker_client_id
+            );
+
+            self.requeue_job(lease);
+        }
+    }
+
+    /// Drops the clients that did not access the grid server within the session timeout and have
+    /// neither jobs without a result nor results to fetch.
+    fn remove_expired_clients(&self) {
+        let Some(session_timeout) = self
+            .configuration
+            .session_timeout
+            .and_then(|session_timeout| chrono::Duration::from_std(session_timeout).ok())
+        else {
+            return;
+        };
+
+        let now = Utc::now();
+
+        // The clients whose jobs or results are still there, which are kept however long they are
+        // idle.
+        let mut busy_client_ids: HashSet<ClientId> = self
+            .client_id_per_job_id
+            .lock()
+            .unwrap()
+            .values()
+            .copied()
+            .collect();
+        busy_client_ids.extend(self.results_per_client_id.lock().unwrap().keys());
+
+        let expired_client_ids: Vec<ClientId> = {
+            let client_information_per_client_id =
+                self.client_information_per_client_id.lock().unwrap();
+            let result_sender_per_client_id = self.result_sender_per_client_id.lock().unwrap();
+
+            client_information_per_client_id
+                .iter()
+                .filter(|(client_id, client_information)| {
+                    now - client_information.last_access > session_timeout
+                        && !busy_client_ids.contains(client_id)
+                        // A client with a connected result stream is still there.
+                        && result_sender_per_client_id
+                            .get(client_id)
+                            .is_none_or(|result_sender| result_sender.is_closed())
+                })
+                .map(|(client_id, _)| *client_id)
+                .collect()
+        };
+
+        for client_id in expired_client_ids {
+            // Persist the expiry. A failure is logged by `persist()`.
+            if self
+                .persist(vec![record(RecordKind::ClientExpired(ClientExpired {
+                    client_id,
+                }))])
+                .is_err()
+            {
+                continue;
+            }
+
+            self.client_id_per_session_token
+                .lock()
+                .unwrap()
+                .retain(|_, session_client_id| *session_client_id != client_id);
+            self.client_information_per_client_id
+                .lock()
+                .unwrap()
+                .remove(&client_id);
+            self.result_sender_per_client_id
+                .lock()
+                .unwrap()
+                .remove(&client_id);
+            self.cancelled_job_ids_per_worker_client_id
+                .lock()
+                .unwrap()
+                .remove(&client_id);
+            {
+                let mut finished_jobs_per_job_id = self.finished_jobs_per_job_id.lock().unwrap();
+
+                finished_jobs_per_job_id
+                    .retain(|_, (finished_client_id, _)| *finished_client_id != client_id);
+                self.retained_job_ids
+                    .lock()
+                    .unwrap()
+                    .retain(|job_id| finished_jobs_per_job_id.contains_key(job_id));
+            }
+            self.job_traces_per_job_id
+                .lock()
+                .unwrap()
+                .retain(|_, (job_client_id, _)| *job_client_id != client_id);
+            self.labels_per_job_id
+                .lock()
+                .unwrap()
+                .retain(|_, (job_client_id, _)| *job_client_id != client_id);
+
+            info!("Session of client {client_id} expired");
+        }
+    }
+
+    /// Returns whether the server was requested to stop, by a signal or by a controller.
+    fn server_was_requested_to_stop(&self) -> bool {
+        // The server was requested to stop.
+        STOP_SERVER.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether no dispatched job waits for its result and no result waits to be fetched.
+    fn is_drained(&self) -> bool {
+        self.leases_per_job_id.lock().unwrap().is_empty()
+            && self
+                .results_per_client_id
+                .lock()
+                .unwrap()
+                .values()
+                .all(Vec::is_empty)
+    }
+
+    /// Waits until the server was requested to stop and then until it is drained, or until the
+    /// drain timeout expired.
+    async fn drain(&self) {
+        let mut interval = tokio::time::interval(Duration::from_millis(100));
+
+        // Wait until the server is requested to stop, e.g. by a signal.
+        while !self.server_was_requested_to_stop() {
+            interval.tick().await;
+        }
+
+        info!(
+            "Server was requested to stop, draining for up to {} seconds ...",
+            self.configuration.drain_timeout.as_secs()
+        );
+
+        // Wake the grid workers that wait for a job, since no job is dispatched anymore.
+        self.job_queued.notify_waiters();
+
+        let drain_deadline = Instant::now() + self.configuration.drain_timeout;
+
+        while !self.is_drained() {
+            if Instant::now() >= drain_deadline {
+                warn!("The drain timeout expired");
+                break;
+            }
+
+            interval.tick().await;
+        }
+
+        // End the result streams, so that their connections are closed.
+        self.result_sender_per_client_id.lock().unwrap().clear();
+    }
+
+    /// Logs the jobs and results that are left when the server stops.
+    fn log_remaining_jobs_and_results(&self) {
+        let queued_job_count: usize = self
+            .jobs_per_service_id_and_version
+            .lock()
+            .unwrap()
+            .values()
+            .flat_map(|jobs_per_service_version| jobs_per_service_version.values())
+            .map(|jobs| jobs.iter().count())
+            .sum();
+        let dispatched_job_count = self.leases_per_job_id.lock().unwrap().len();
+        let result_count: usize = self
+            .results_per_client_id
+            .lock()
+            .unwrap()
+            .values()
+            .map(Vec::len)
+            .sum();
+
+        let remaining = format!(
+            "{queued_job_count} queued jobs, {dispatched_job_count} dispatched jobs without a \
+             result and {result_count} results that were not fetched"
+        );
+
+        // Everything was persisted as it happened, so it is restored on the next start.
+        match &self.configuration.data_directory {
+            Some(data_directory) => info!(
+                "Stopping the server, {remaining} remain persisted in \"{}\"",
+                data_directory.display()
+            ),
+            None if queued_job_count + dispatched_job_count + result_count > 0 => {
+                warn!("Stopping the server, {remaining} are lost")
+            }
+            None => info!("Stopping the server"),
+        }
+    }
+
+    /// Returns the given jobs of the given client that have every label of the given filter, in
+    /// order, or all of its jobs with these labels ordered by job ID if no jobs are given. Returns
+    /// the given jobs if the filter is empty.
+    fn job_ids_with_labels(
+        &self,
+        client_id: ClientId,
+        job_ids: &[JobId],
+        label_filter: &Labels,
+    ) -> Vec<JobId> {
+        if label_filter.is_empty() {
+            return job_ids.to_vec();
+        }
+
+        let labels_per_job_id = self.labels_per_job_id.lock().unwrap();
+
+        let has_labels = |job_id: &JobId| {
+            labels_per_job_id
+                .get(job_id)
+                .is_some_and(|(job_client_id, labels)| {
+                    *job_client_id == client_id && labels_match(labels, label_filter)
+                })
+        };
+
+        if !job_ids.is_empty() {
+            return job_ids.iter().copied().filter(has_labels).collect();
+        }
+
+        let mut job_ids: Vec<JobId> = labels_per_job_id
+            .keys()
+            .copied()
+            .filter(has_labels)
+            .collect();
+        job_ids.sort();
+
+        job_ids
+    }
+
+    /// Returns the ID of the client with the given session token, if it has the given role, and
+    /// refreshes its last access time.
+    fn authenticate(&self, session_token: &str, role: ClientRole) -> Result<ClientId, Status> {
+        let client_id = self
+            .client_id_per_session_token
+            .lock()
+            .unwrap()
+            .get(session_token)
+            .copied()
+            .ok_or_else(|| Status::unauthenticated("Unknown session token"))?;
+
+        let client_role = self
+            .client_information_per_client_id
+            .lock()
+            .unwrap()
+            .get(&client_id)
+            .map(|client_information| client_information.role);
+
+        if client_role != Some(role) {
+            return Err(Status::permission_denied(format!(
+                "Client {client_id} is not registered as {}",
+                role.as_str_name()
+            )));
+        }
+
+        self.update_client_last_access_time(client_id);
+
+        Ok(client_id)
+    }
+
+    /// Refreshes the last access time of the given client, which keeps its session alive.
+    fn update_client_last_access_time(&self, client_id: ClientId) {
+        if let Some(client_information) = self
+            .client_information_per_client_id
+            .lock()
+            .unwrap()
+            .get_mut(&client_id)
+        {
+            client_information.last_access = Utc::now();
+        }
+    }
+}
+
+/// The implementation of the server interface for the server.
+#[tonic::async_trait]
+impl GridServer for GridServerImpl {
+    async fn client_acknowledge_results(
+        &self,
+        request: Request<RequestFromClientResultAcknowledge>,
+    ) -> Result<Response<ResponseToClientResultAcknowledge>, Status> {
+        let request = request.get_ref();
+
+        // Authenticate the client and update its last access time.
+        let client_id = self.authenticate(&request.session_token, ClientRole::Submitter)?;
+
+        let job_ids: HashSet<JobId> = request.job_ids.iter().copied().collect();
+
+        self.take_results(client_id, |result| job_ids.contains(&result.job_id));
+
+        Ok(Response::new(ResponseToClientResultAcknowledge {}))
+    }
+
+    async fn client_cancel_job(
+        &self,
+        request: Request<RequestFromClientJobCancel>,
+    ) -> Result<Response<ResponseToClientJobCancel>, Status> {
+        let request = request.get_ref();
+
+        // Authenticate the client and update its last access time.
+        let client_id = self.authenticate(&request.session_token, ClientRole::Submitter)?;
+
+        let job_ids = self.job_ids_with_labels(client_id, &request.job_ids, &request.label_filter);
+        let cancelled_job_ids = self.cancel_jobs(client_id, &job_ids)?;
+
+        if !cancelled_job_ids.is_empty() {
+            info!("Cancelled jobs with IDs {cancelled_job_ids:?} of client {client_id}");
+        }
+
+        Ok(Response::new(ResponseToClientJobCancel {
+            cancelled_job_ids,
+        }))
+    }
+
+    type ClientDownloadResultsStream =
+        Pin<Box<dyn Stream<Item = Result<grid_server_interface::Result, Status>> + Send>>;
+
+    async fn client_download_results(
+        &self,
+        request: Request<RequestFromClientResultFetch>,
+    ) -> Result<Response<Self::ClientDownloadResultsStream>, Status> {
+        let request = request.get_ref();
+
+        // Authenticate the client and update its last access time.
+        let client_id = self.authenticate(&request.session_token, ClientRole::Submitter)?;
+
+        // Keep the results until the client acknowledges them.
+        let results: Vec<grid_server_interface::Result> = self
+            .results_per_client_id
+            .lock()
+            .unwrap()
+            .get(&client_id)
+            .into_iter()
+            .flatten()
+            .filter(|result| labels_match(&result.labels, &request.label_filter))
+            .cloned()
+            .collect();
+
+        if !results.is_empty() {
+            info!("Sending results to client {client_id}");
+        }
+
+        // Split the result data of every result into chunks, which are sent as results with the
+        // same job ID.
+        let chunks: Vec<_> = results
+            .into_iter()
+            .flat_map(split_result_into_chunks)
+            .map(Ok)
+            .collect();
+
+        Ok(Response::new(Box::pin(tokio_stream::iter(chunks))))
+    }
+
+    async fn client_fetch_results(
+        &self,
+        request: Request<RequestFromClientResultFetch>,
+    ) -> Result<Response<ResponseToClientResultFetch>, Status> {
+        let request = request.get_ref();
+
+        // Authenticate the client and update its last access time.
+        let client_id = self.authenticate(&request.session_token, ClientRole::Submitter)?;
+
+        let results = self.take_results(client_id, |result| {
+            labels_match(&result.labels, &request.label_filter)
+        });
+
+        if !results.is_empty() {
+            info!("Sending results to client {client_id}");
+        }
+
+        Ok(Response::new(ResponseToClientResultFetch { results }))
+    }
+
+    type ClientStreamResultsStream =
+        Pin<Box<dyn Stream<Item = Result<grid_server_interface::Result, Status>> + Send>>;
+
+    async fn client_stream_results(
+        &self,
+        request: Request<RequestFromClientResultStream>,
+    ) -> Result<Response<Self::ClientStreamResultsStream>, Status> {
+        let request = request.get_ref();
+
+        // Authenticate the client and update its last access time.
+        let client_id = self.authenticate(&request.session_token, ClientRole::Submitter)?;
+
+        let (result_sender, result_receiver) = unbounded_channel();
+
+        let fetched_job_ids = {
+            let mut results_per_client_id = self.results_per_client_id.lock().unwrap();
+            let mut result_sender_per_client_id = self.result_sender_per_client_id.lock().unwrap();
+            let mut job_ids: Vec<JobId> = vec![];
+
+            // Send the results that arrived while the client was not connected.
+            if let Some(results) = results_per_client_id.remove(&client_id) {
+                info!("Sending buffered results to client {client_id}");
+
+                job_ids = results.iter().map(|result| result.job_id).collect();
+
+                for chunk in results.into_iter().flat_map(split_result_into_chunks) {
+                    let _ = result_sender.send(Ok(chunk));
+                }
+            }
+
+            // Stream all further results. This ends a previous result stream of the client.
+            result_sender_per_client_id.insert(client_id, result_sender);
+
+            job_ids
+        };
+
+        if !fetched_job_ids.is_empty() {
+            // Persist that the results were fetched, without holding the results. A failure is
+            // logged by `persist()`.
+            let _ = self.persist(vec![record(RecordKind::ResultsFetched(ResultsFetched {
+                client_id,
+                job_ids: fetched_job_ids.clone(),
+            }))]);
+
+            self.trace_jobs(&fetched_job_ids, JobEvent::ResultFetched);
+            self.retain_finished_jobs(&fetched_job_ids);
+        }
+
+        info!("Streaming results to client {client_id}");
+
+        Ok(Response::new(Box::pin(UnboundedReceiverStream::new(
+            result_receiver,
+        ))))
+    }
+
+    async fn client_get_job_status(
+        &self,
+        request: Request<RequestFromClientJobStatusGet>,
+    ) -> Result<Response<ResponseToClientJobStatusGet>, Status> {
+        let request = request.get_ref();
+
+        // Authenticate the client and update its last access time.
+        let client_id = self.authenticate(&request.session_token, ClientRole::Submitter)?;
+
+        let job_ids = self.job_ids_with_labels(client_id, &request.job_ids, &request.label_filter);
+        let requested_job_ids: HashSet<JobId> = job_ids.iter().copied().collect();
+
+        let jobs_per_service_id_and_version = self.jobs_per_service_id_and_version.lock().unwrap();
+        let client_id_per_job_id = self.client_id_per_job_id.lock().unwrap();
+
+        // Determine the queue positions and priorities of the requested jobs.
+        let mut queue_position_and_priority_per_job_id = HashMap::new();
+
+        for jobs_per_service_version in jobs_per_service_id_and_version.values() {
+            for jobs in jobs_per_service_version.values() {
+                // Skip jobs that got a result after their lease expired.
+                let queued_jobs = jobs
+                    .iter()
+                    .filter(|queued_job| client_id_per_job_id.contains_key(&queued_job.job.job_id));
+
+                for (queue_position, queued_job) in queued_jobs.enumerate() {
+                    if requested_job_ids.contains(&queued_job.job.job_id) {
+                        queue_position_and_priority_per_job_id.insert(
+                            queued_job.job.job_id,
+                            (queue_position as u64, queued_job.priority),
+                        );
+                    }
+                }
+            }
+        }
+
+        let leases_per_job_id = self.leases_per_job_id.lock().unwrap();
+        let finished_jobs_per_job_id = self.finished_jobs_per_job_id.lock().unwrap();
+        let labels_per_job_id = self.labels_per_job_id.lock().unwrap();
+
+        let job_status = job_ids
+            .iter()
+            .map(|job_id| {
+                let mut job_status = JobStatus {
+                    job_id: *job_id,
+                    ..Default::default()
+                };
+
+                if let Some((_, labels)) = labels_per_job_id
+                    .get(job_id)
+                    .filter(|(job_client_id, _)| *job_client_id == client_id)
+                {
+                    job_status.labels = labels.clone();
+                }
+
+                // The job is still open.
+                if client_id_per_job_id.get(job_id) == Some(&client_id) {
+                    if let Some((queue_position, priority)) =
+                        queue_position_and_priority_per_job_id.get(job_id)
+                    {
+                        job_status.set_state(JobState::Queued);
+                        job_status.queue_position = Some(*queue_position);
+                        job_status.priority = Some(*priority);
+                    } else if let Some(lease) = leases_per_job_id.get(job_id) {
+                        job_status.set_state(JobState::Dispatched);
+                        job_status.worker_client_id = Some(lease.worker_client_id);
+                        job_status.priority = Some(lease.priority);
+                    }
+                }
+                // The job is finished.
+                else if let Some((_, job_state)) = finished_jobs_per_job_id
+                    .get(job_id)
+                    .filter(|(job_client_id, _)| *job_client_id == client_id)
+                {
+                    job_status.set_state(*job_state);
+                }
+
+                job_status
+            })
+            .collect();
+
+        Ok(Response::new(ResponseToClientJobStatusGet { job_status }))
+    }
+
+    async fn client_get_job_trace(
+        &self,
+        request: Request<RequestFromClientJobTraceGet>,
+    ) -> Result<Response<ResponseToClientJobTraceGet>, Status> {
+        let request = request.get_ref();
+
+        // Authenticate the client and update its last access time.
+        let client_id = self.authenticate(&request.session_token, ClientRole::Submitter)?;
+
+        let job_traces_per_job_id = self.job_traces_per_job_id.lock().unwrap();
+
+        let job_traces = request
+            .job_ids
+            .iter()
+            .map(|job_id| {
+                job_traces_per_job_id
+                    .get(job_id)
+                    .filter(|(job_client_id, _)| *job_client_id == client_id)
+                    .map(|(_, job_trace)| job_trace.clone())
+                    .unwrap_or(JobTrace {
+                        job_id: *job_id,
+                        ..Default::default()
+                    })
+            })
+            .collect();
+
+        Ok(Response::new(ResponseToClientJobTraceGet { job_traces }))
+    }
+
+    async fn client_register(
+        &self,
+        request: Request<RequestFromClientRegister>,
+    ) -> Result<Response<ResponseToClientRegister>, Status> {
+        // TODO: Grant or deny a client ID according to the request.
+        warn!("TODO: `client_register()`: grant or deny a client ID according to the request.");
+
+        let request = request.get_ref();
+
+        let role = ClientRole::try_from(request.role)
+            .map_err(|_| Status::invalid_argument(format!("Unknown role {}", request.role)))?;
+
+        // Only the clients that know the role's secret may take it, if it has one.
+        if let Some(role_secret) = self.configuration.role_secret_per_role.get(&role) {
+            if !role_secrets_equal(&request.role_secret, role_secret) {
+                warn!(
+                    "Denying the role {} to client \"{}\" of user \"{}\", because its secret is \
+                     wrong",
+                    role.as_str_name(),
+                    request.client_description,
+                    request.user_id
+                );
+
+                return Err(Status::permission_denied(format!(
+                    "Wrong secret for the role {}",
+                    role.as_str_name()
+                )));
+            }
+        }
+
+        // Take the current client ID and increase the next client ID. The client ID is skipped if
+        // the client can not be persisted, so that the lock is not held while persisting.
+        let client_id = {
+            let mut next_client_id = self.next_client_id.lock().unwrap();
+            let client_id = *next_client_id;
+
+            *next_client_id += 1;
+            client_id
+        };
+
+        // Create a random session token, which can not be guessed by other clients.
+        let session_token = format!("{:032x}", rand::random::<u128>());
+
+        // Save client information.
+        {
+            // Persist the client before handing out its ID.
+            self.persist(vec![record(RecordKind::ClientRegistered(
+                ClientRegistered {
+                    client_id,
+                    client_description: request.client_description.clone(),
+                    host_id: request.host_id.clone(),
+                    user_id: request.user_id.clone(),
+                    session_token: session_token.clone(),
+                    role: role.into(),
+                },
+            ))])?;
+
+            self.client_id_per_session_token
+                .lock()
+                .unwrap()
+                .insert(session_token.clone(), client_id);
+
+            let mut client_information_per_client_id =
+                self.client_information_per_client_id.lock().unwrap();
+
+            client_information_per_client_id
+                .entry(client_id)
+                .or_insert(ClientInformation {
+                    client_description: request.client_description.clone(),
+                    host_id: request.host_id.clone(),
+                    last_access: Utc::now(),
+                    role,
+                    user_id: request.user_id.clone(),
+                });
+
+            // client_information_per_client_id
+        }
+
+        Ok(Response::new(ResponseToClientRegister {
+            client_id,
+            session_token,
+        }))
+    }
+
+    async fn client_stream_jobs(
+        &self,
+        request: Request<Streaming<RequestFromClientJobsSubmit>>,
+    ) -> Result<Response<ResponseToClientJobsSubmit>, Status> {
+        let mut request_stream = request.into_inner();
+        let mut requests: Vec<RequestFromClientJobsSubmit> = vec![];
+        // The client and its session token, once the first message authenticated it.
+        let mut maybe_client_id_and_session_token: Option<(ClientId, String)> = None;
+        let mut job_count = 0;
+        let mut byte_count = 0;
+
+        // Collect all jobs of the stream, so that they are queued together.
+        while let Some(request) = request_stream.message().await? {
+            let client_id = match &maybe_client_id_and_session_token {
+                Some((client_id, session_token)) => {
+                    if request.session_token != *session_token {
+                        return Err(Status::invalid_argument(
+                            "All jobs of a stream must be submitted by the same client",
+                        ));
+                    }
+
+                    *client_id
+                }
+                None => {
+                    // Authenticate the client before accepting its jobs.
+                    let client_id =
+                        self.authenticate(&request.session_token, ClientRole::Submitter)?;
+                    maybe_client_id_and_session_token =
+                        Some((client_id, request.session_token.clone()));
+
+                    client_id
+                }
+            };
+
+            byte_count += request
+                .job_data
+                .iter()
+                .map(|job_data| job_data.len() as u64)
+                .sum::<u64>();
+
+            match requests.last_mut() {
+                // The message contains the next chunk of the previous message's last job data.
+                Some(previous_request) if previous_request.job_data_continues => {
+                    let (Some(job_data), [chunk]) = (
+                        previous_request.job_data.last_mut(),
+                        request.job_data.as_slice(),
+                    ) else {
+                        return Err(Status::invalid_argument(
+                            "A message that continues job data must contain a single chunk",
+                        ));
+                    };
+
+                    self.check_data_size(job_data.len() + chunk.len())?;
+
+                    job_data.extend(chunk);
+                    previous_request.job_data_continues = request.job_data_continues;
+                }
+                _ => {
+                    for job_data in &request.job_data {
+                        self.check_data_size(job_data.len())?;
+                    }
+
+                    job_count += request.job_data.len() as u64;
+                    requests.push(request);
+                }
+            }
+
+            // Reject the stream as soon as the buffered jobs exceed a limit of the client or its
+            // user.
+            self.check_limits(client_id, job_count, byte_count)?;
+        }
+
+        if requests
+            .last()
+            .is_some_and(|request| request.job_data_continues)
+        {
+            return Err(Status::invalid_argument(
+                "The stream ended before the last job data was complete",
+            ));
+        }
+
+        // The stream was empty.
+        let Some((client_id, _)) = maybe_client_id_and_session_token else {
+            return Ok(Response::new(ResponseToClientJobsSubmit {
+                job_ids: vec![],
+            }));
+        };
+
+        // The server was requested to stop.
+        if self.server_was_requested_to_stop() {
+            info!("Server was requested to stop, rejecting new jobs from client");
+
+            // Do not accept any new jobs.
+            return Ok(Response::new(ResponseToClientJobsSubmit {
+                job_ids: vec![],
+            }));
+        }
+
+        Ok(Response::new(ResponseToClientJobsSubmit {
+            job_ids: self.queue_jobs(client_id, requests)?,
+        }))
+    }
+
+    async fn client_submit_job(
+        &self,
+        request: Request<RequestFromClientJobSubmit>,
+    ) -> Result<Response<ResponseToClientJobSubmit>, Status> {
+        let request = request.get_ref();
+
+        // Authenticate the client and update its last access time.
+        let client_id = self.authenticate(&request.session_token, ClientRole::Submitter)?;
+
+        // The server was requested to stop.
+        if self.server_was_requested_to_stop() {
+            info!("Server was requested to stop, rejecting new job from client");
+
+            // Do not accept any new jobs.
+            return Ok(Response::new(ResponseToClientJobSubmit { job_id: None }));
+        }
+
+        let job_ids = self.queue_jobs(
+            client_id,
+            vec![RequestFromClientJobsSubmit {
+                session_token: request.session_token.clone(),
+                service_id: request.service_id,
+                service_version: request.service_version,
+                job_data: vec![request.job_data.clone()],
+                priority: request.priority,
+                service_version_constraint: request.service_version_constraint.clone(),
+                labels: request.labels.clone(),
+                job_data_continues: false,
+            }],
+        )?;
+
+        // Return the job ID.
+        Ok(Response::new(ResponseToClientJobSubmit {
+            job_id: job_ids.first().copied(),
+        }))
+    }
+
+    async fn client_upload_job(
+        &self,
+        request: Request<Streaming<RequestFromClientJobSubmit>>,
+    ) -> Result<Response<ResponseToClientJobSubmit>, Status> {
+        let mut request_stream = request.into_inner();
+        let mut maybe_request: Option<RequestFromClientJobSubmit> = None;
+
+        // Concatenate the job data of all chunks, so that the job is queued once it arrived.
+        while let Some(chunk) = request_stream.message().await? {
+            match &mut maybe_request {
+                Some(request) => {
+                    if chunk.session_token != request.session_token {
+                        return Err(Status::invalid_argument(
+                            "All chunks of a job must be uploaded by the same client",
+                        ));
+                    }
+
+                    self.check_data_size(request.job_data.len() + chunk.job_data.len())?;
+
+                    request.job_data.extend(chunk.job_data);
+                }
+                None => {
+                    // Authenticate the client before accepting its job data.
+                    self.authenticate(&chunk.session_token, ClientRole::Submitter)?;
+                    self.check_data_size(chunk.job_data.len())?;
+
+                    maybe_request = Some(chunk);
+                }
+            }
+        }
+
+        let Some(request) = maybe_request else {
+            return Err(Status::invalid_argument("The upload of the job was empty"));
+        };
+
+        self.client_submit_job(Request::new(request)).await
+    }
+
+    async fn client_submit_jobs(
+        &self,
+        request: Request<RequestFromClientJobsSubmit>,
+    ) -> Result<Response<ResponseToClientJobsSubmit>, Status> {
+        let request = request.into_inner();
+
+        // Authenticate the client and update its last access time.
+        let client_id = self.authenticate(&request.session_token, ClientRole::Submitter)?;
+
+        if request.job_data_continues {
+            return Err(Status::invalid_argument(
+                "Job data can only continue in a stream of jobs",
+            ));
+        }
+
+        // The server was requested to stop.
+        if self.server_was_requested_to_stop() {
+            info!("Server was requested to stop, rejecting new jobs from client");
+
+            // Do not accept any new jobs.
+            return Ok(Response::new(ResponseToClientJobsSubmit {
+                job_ids: vec![],
+            }));
+        }
+
+        Ok(Response::new(ResponseToClientJobsSubmit {
+            job_ids: self.queue_jobs(client_id, vec![request])?,
+        }))
+    }
+
+    async fn controller_drain_server(
+        &self,
+        request: Request<RequestFromControllerServerDrain>,
+    ) -> Result<Response<ResponseToControllerServerDrain>, Status> {
+        let request = request.get_ref();
+
+        // Authenticate the client and update its last access time.
+        let client_id = self.authenticate(&request.session_token, ClientRole::Controller)?;
+
+        info!("Client {client_id} requested the server to stop");
+
+        STOP_SERVER.store(true, Ordering::Relaxed);
+
+        Ok(Response::new(ResponseToControllerServerDrain {}))
+    }
+
+    async fn controller_flush_result_cache(
+        &self,
+        request: Request<RequestFromControllerResultCacheFlush>,
+    ) -> Result<Response<ResponseToControllerResultCacheFlush>, Status> {
+        let request = request.get_ref();
+
+        // Authenticate the client and update its last access time.
+        let client_id = self.authenticate(&request.session_token, ClientRole::Controller)?;
+
+        let flushed_entry_count = self.result_cache.lock().unwrap().flush();
+
+        info!("Client {client_id} flushed {flushed_entry_count} cached results");
+
+        Ok(Response::new(ResponseToControllerResultCacheFlush {
+            flushed_entry_count,
+        }))
+    }
+
+    async fn controller_get_dead_letters(
+        &self,
+        request: Request<RequestFromControllerDeadLettersGet>,
+    ) -> Result<Response<ResponseToControllerDeadLettersGet>, Status> {
+        let request = request.get_ref();
+
+        // Authenticate the client and update its last access time.
+        self.authenticate(&request.session_token, ClientRole::Controller)?;
+
+        Ok(Response::new(ResponseToControllerDeadLettersGet {
+            dead_letters: self
+                .dead_letters_per_job_id
+                .lock()
+                .unwrap()
+                .values()
+                .cloned()
+                .collect(),
+        }))
+    }
+
+    async fn controller_get_status(
+        &self,
+        request: Request<RequestFromControllerStatusGet>,
+    ) -> Result<Response<ResponseToControllerStatusGet>, Status> {
+        let request = request.get_ref();
+
+        // Authenticate the client and update its last access time.
+        self.authenticate(&request.session_token, ClientRole::Controller)?;
+
+        // Add the clients with the number of their pending results.
+        let clients = {
+            let client_information_per_client_id =
+                self.client_information_per_client_id.lock().unwrap();
+            let results_per_client_id = self.results_per_client_id.lock().unwrap();
+
+            let mut clients: Vec<ClientStatus> = client_information_per_client_id
+                .iter()
+                .map(|(client_id, client_information)| ClientStatus {
+                    client_id: *client_id,
+                    client_description: client_information.client_description.clone(),
+                    host_id: client_information.host_id.clone(),
+                    user_id: client_information.user_id.clone(),
+                    last_access: client_information.last_access.to_rfc3339(),
+                    pending_result_count: results_per_client_id.get(client_id).map_or(0, Vec::len)
+                        as u64,
+                    role: client_information.role.into(),
+                })
+                .collect();
+            clients.sort_by_key(|client| client.client_id);
+
+            clients
+        };
+
+        // Add the queue depths, without the jobs that already got a result.
+        let queues = {
+            let jobs_per_service_id_and_version =
+                self.jobs_per_service_id_and_version.lock().unwrap();
+            let client_id_per_job_id = self.client_id_per_job_id.lock().unwrap();
+
+            let mut queues: Vec<QueueStatus> = jobs_per_service_id_and_version
+                .iter()
+                .flat_map(|(service_id, jobs_per_service_version)| {
+                    jobs_per_service_version
+                        .iter()
+                        .flat_map(|(service_versions, jobs)| {
+                            let mut queued_job_count_per_priority: BTreeMap<Priority, u64> =
+                                BTreeMap::new();
+
+                            for queued_job in jobs.iter().filter(|queued_job| {
+                                client_id_per_job_id.contains_key(&queued_job.job.job_id)
+                            }) {
+                                *queued_job_count_per_priority
+                                    .entry(queued_job.priority)
+                                    .or_default() += 1;
+                            }
+
+                            queued_job_count_per_priority.into_iter().map(
+                                |(priority, queued_job_count)| QueueStatus {
+                                    service_id: *service_id,
+                                    service_version: service_versions.oldest,
+                                    queued_job_count,
+                                    priority,
+                                    newer_service_versions: service_versions.newer,
+                                },
+                            )
+                        })
+                })
+                .collect();
+            queues.sort_by_key(|queue| {
+                (
+                    queue.service_id,
+                    queue.service_version,
+                    queue.newer_service_versions,
+                    Reverse(queue.priority),
+                )
+            });
+
+            queues
+        };
+
+        // Add the jobs in flight, per worker.
+        let workers = {
+            let mut dispatched_job_ids_per_worker_client_id: BTreeMap<ClientId, Vec<JobId>> =
+                BTreeMap::new();
+
+            for (job_id, lease) in self.leases_per_job_id.lock().unwrap().iter() {
+                dispatched_job_ids_per_worker_client_id
+                    .entry(lease.worker_client_id)
+                    .or_default()
+                    .push(*job_id);
+            }
+
+            dispatched_job_ids_per_worker_client_id
+                .into_iter()
+                .map(|(worker_client_id, mut dispatched_job_ids)| {
+                    dispatched_job_ids.sort();
+
+                    WorkerStatus {
+                        worker_client_id,
+                        dispatched_job_ids,
+                    }
+                })
+                .collect()
+        };
+
+        Ok(Response::new(ResponseToControllerStatusGet {
+            clients,
+            queues,
+            workers,
+            result_cache: Some(self.result_cache.lock().unwrap().status()),
+        }))
+    }
+
+    type WorkerDownloadJobStream = Pin<Box<dyn Stream<Item = Result<Job, Status>> + Send>>;
+
+    async fn worker_download_job(
+        &self,
+        request: Request<RequestFromWorkerJobDownload>,
+    ) -> Result<Response<Self::WorkerDownloadJobStream>, Status> {
+        let request = request.get_ref();
+
+        // Authenticate the client and update its last access time.
+        let worker_client_id = self.authenticate(&request.session_token, ClientRole::Worker)?;
+
+        let job_id = request.job_id;
+
+        // Only the worker that holds the lease of the job may download its data.
+        let job_data = match self.leases_per_job_id.lock().unwrap().get(&job_id) {
+            Some(lease) if lease.worker_client_id == worker_client_id => lease.job.job_data.clone(),
+            _ => {
+                return Err(Status::not_found(format!(
+                    "Worker {worker_client_id} does not hold the lease of job with ID {job_id}"
+                )))
+            }
+        };
+
+        info!("Sending data of job with ID {job_id} to worker {worker_client_id}");
+
+        let mut chunks = vec![];
+
+        for job_data in split_into_chunks(job_data) {
+            chunks.push(Ok(Job {
+                job_id,
+                job_data,
+                job_data_size: 0,
+                labels: Labels::new(),
+            }));
+        }
+
+        Ok(Response::new(Box::pin(tokio_stream::iter(chunks))))
+    }
+
+    async fn worker_server_exchange(
+        &self,
+        request: Request<RequestFromWorkerExchange>,
+    ) -> Result<Response<ResponseToWorkerExchange>, Status> {
+        let request = request.get_ref();
+
+        // Authenticate the client and update its last access time.
+        let worker_client_id = self.authenticate(&request.session_token, ClientRole::Worker)?;
+
+        // There is a result from the worker.
+        if let Some(result_from_worker) = &request.result_from_worker {
+            self.check_data_size(result_from_worker.result_data.len())?;
+            self.add_result(worker_client_id, result_from_worker);
+        }
+
+        // Tell the worker about its jobs that were cancelled. They are taken only once the
+        // exchange cannot be rejected anymore, so that the worker does not miss them.
+        let cancelled_job_ids = self
+            .cancelled_job_ids_per_worker_client_id
+            .lock()
+            .unwrap()
+            .remove(&worker_client_id)
+            .unwrap_or_default();
+
+        let jobs: Vec<(JobQuery, QueuedJob)> = {
+            // The server was requested to stop.
+            if self.server_was_requested_to_stop() {
+                info!("Server was requested to stop, not passing jobs to worker");
+
+                // Do not pass a jobs to workers.
+                return Ok(Response::new(ResponseToWorkerExchange {
+                    job: None,
+                    lease_milliseconds: 0,
+                    cancelled_job_ids,
+                    prefetched_jobs: vec![],
+                }));
+            }
+
+            // Try to get jobs for the given request.
+            match &request.query_job_from_server {
+                Some(job_query) => {
+                    // Remember the newest version of the service, for the jobs that require the
+                    // latest version.
+                    self.newest_service_version_per_service_id
+                        .lock()
+                        .unwrap()
+                        .entry(job_query.service_id)
+                        .and_modify(|service_version| {
+                            *service_version = (*service_version).max(job_query.service_version)
+                        })
+                        .or_insert(job_query.service_version);
+
+                    let job_count = job_query.job_count.clamp(1, MAX_JOB_COUNT) as usize;
+                    let wait_duration =
+                        Duration::from_millis(request.wait_milliseconds).min(MAX_WAIT_DURATION);
+                    let wait_deadline = tokio::time::Instant::now() + wait_duration;
+
+                    loop {
+                        // Listen for queued jobs before looking, so that no job is missed.
+                        let job_queued = self.job_queued.notified();
+                        tokio::pin!(job_queued);
+                        job_queued.as_mut().enable();
+
+                        // The server was requested to stop while the worker waited.
+                        if self.server_was_requested_to_stop() {
+                            break vec![];
+                        }
+
+                        let jobs = self.pop_jobs(job_query, job_count, worker_client_id);
+
+                        if !jobs.is_empty() {
+                            break jobs
+                                .into_iter()
+                                .map(|queued_job| (job_query.clone(), queued_job))
+                                .collect();
+                        }
+
+                        // Wait until a job is queued, or give up.
+                        if tokio::time::timeout_at(wait_deadline, job_queued)
+                            .await
+                            .is_err()
+                        {
+                            break vec![];
+                        }
+                    }
+                }
+                None => vec![],
+            }
+        };
+
+        let mut sent_data_size = 0;
+
+        // Send every leased job to the worker.
+        let jobs = jobs
+            .into_iter()
+            .map(|(job_query, queued_job)| {
+                let job = queued_job.job;
+                let job_id = job.job_id;
+
+                info!("Sending job with ID {job_id} to worker {worker_client_id}");
+
+                self.metrics.job_dispatched(
+                    job_query.service_id,
+                    job_query.service_version,
+                    queued_job.queued_at.elapsed(),
+                );
+                self.trace_jobs(&[job_id], JobEvent::Dispatched { worker_client_id });
+
+                // Remember that the worker may submit the job's result.
+                self.worker_client_ids_per_job_id
+                    .lock()
+                    .unwrap()
+                    .entry(job_id)
+                    .or_default()
+                    .insert(worker_client_id);
+
+                // Leave out the job data that does not fit into the response. The worker downloads
+                // it via `worker_download_job()`.
+                let job_data_size = job.job_data.len();
+
+                if sent_data_size + job_data_size > DATA_CHUNK_SIZE {
+                    return Job {
+                        job_id,
+                        job_data: vec![],
+                        job_data_size: job_data_size as u64,
+                        labels: job.labels,
+                    };
+                }
+
+                sent_data_size += job_data_size;
+
+                job
+            })
+            .collect::<Vec<_>>();
+
+        // Persist the dispatches. A failure is logged by `persist()`.
+        if !jobs.is_empty() {
+            let _ = self.persist(
+                jobs.iter()
+                    .map(|job| {
+                        record(RecordKind::JobDispatched(JobDispatched {
+                            job_id: job.job_id,
+                            worker_client_id,
+                        }))
+                    })
+                    .collect(),
+            );
+        }
+
+        let mut jobs = jobs.into_iter();
+
+        Ok(Response::new(ResponseToWorkerExchange {
+            job: jobs.next(),
+            lease_milliseconds: self.configuration.lease_duration.as_millis() as u64,
+            cancelled_job_ids,
+            prefetched_jobs: jobs.collect(),
+        }))
+    }
+
+    async fn worker_extend_lease(
+        &self,
+        request: Request<RequestFromWorkerLeaseExtend>,
+    ) -> Result<Response<ResponseToWorkerLeaseExtend>, Status> {
+        let request = request.get_ref();
+
+        // Authenticate the client and update its last access time.
+        let worker_client_id = self.authenticate(&request.session_token, ClientRole::Worker)?;
+
+        let deadline = Instant::now() + self.configuration.lease_duration;
+        let mut leases_per_job_id = self.leases_per_job_id.lock().unwrap();
+
+        // Extend the leases held by the worker and collect the others.
+        let lost_job_ids = request
+            .job_ids
+            .iter()
+            .filter(|job_id| match leases_per_job_id.get_mut(job_id) {
+                Some(lease) if lease.worker_client_id == worker_client_id => {
+                    lease.deadline = deadline;
+                    false
+                }
+                _ => true,
+            })
+            .copied()
+            .collect::<Vec<_>>();
+
+        for job_id in &lost_job_ids {
+            warn!("Worker {worker_client_id} does not hold the lease of job with ID {job_id}");
+        }
+
+        Ok(Response::new(ResponseToWorkerLeaseExtend { lost_job_ids }))
+    }
+
+    async fn worker_submit_result(
+        &self,
+        request: Request<RequestFromWorkerResultSubmit>,
+    ) -> Result<Response<ResponseToWorkerResultSubmit>, Status> {
+        let request = request.get_ref();
+
+        // Authenticate the client and update its last access time.
+        let worker_client_id = self.authenticate(&request.session_token, ClientRole::Worker)?;
+
+        // There is a result.
+        if let Some(result) = &request.result {
+            self.check_data_size(result.result_data.len())?;
+            self.add_result(worker_client_id, result);
+        }
+
+        Ok(Response::new(ResponseToWorkerResultSubmit {}))
+    }
+
+    async fn worker_upload_result(
+        &self,
+        request: Request<Streaming<RequestFromWorkerResultSubmit>>,
+    ) -> Result<Response<ResponseToWorkerResultSubmit>, Status> {
+        let mut request_stream = request.into_inner();
+        let mut maybe_request: Option<RequestFromWorkerResultSubmit> = None;
+
+        // Concatenate the result data of all chunks, so that the result is added once it arrived.
+        while let Some(chunk) = request_stream.message().await? {
+            match &mut maybe_request {
+                Some(request) => {
+                    if chunk.session_token != request.session_token {
+                        return Err(Status::invalid_argument(
+                            "All chunks of a result must be uploaded by the same client",
+                        ));
+                    }
+
+                    let result_data = chunk.result.unwrap_or_default().result_data;
+
+                    if let Some(result) = &mut request.result {
+                        self.check_data_size(result.result_data.len() + result_data.len())?;
+
+                        result.result_data.extend(result_data);
+                    }
+                }
+                None => {
+                    // Authenticate the worker before accepting its result data.
+                    self.authenticate(&chunk.session_token, ClientRole::Worker)?;
+
+                    if let Some(result) = &chunk.result {
+                        self.check_data_size(result.result_data.len())?;
+                    }
+
+                    maybe_request = Some(chunk);
+                }
+            }
+        }
+
+        let Some(request) = maybe_request else {
+            return Err(Status::invalid_argument(
+                "The upload of the result was empty",
+            ));
+        };
+
+        self.worker_submit_result(Request::new(request)).await
+    }
+}
+
+/// Splits the result data of the given result into chunks, see `split_into_chunks()`, which are
+/// sent as results with the same job ID and the size of the whole result data.
+fn split_result_into_chunks(
+    result: grid_server_interface::Result,
+) -> Vec<grid_server_interface::Result> {
+    let result_data_size = result.result_data.len() as u64;
+
+    split_into_chunks(result.result_data)
+        .into_iter()
+        .map(|result_data| grid_server_interface::Result {
+            job_id: result.job_id,
+            result_data,
+            status: result.status,
+            error_message: result.error_message.clone(),
+            labels: result.labels.clone(),
+            result_data_size,
+        })
+        .collect()
+}
+
+/// Converts the given dead letter into a record of the store.
+fn dead_letter_added_from_dead_letter(dead_letter: &DeadLetter) -> DeadLetterAdded {
+    let job = dead_letter.job.clone().unwrap_or_default();
+
+    DeadLetterAdded {
+        job_id: job.job_id,
+        client_id: dead_letter.client_id,
+        service_id: dead_letter.service_id,
+        service_version: dead_letter.service_version,
+        job_data: job.job_data,
+        attempts: dead_letter.attempts,
+        error_message: dead_letter.error_message.clone(),
+        failed_at: dead_letter.failed_at.clone(),
+        labels: job.labels,
+    }
+}
+
+/// Converts the given outcome of a finished job, as persisted, into its state.
+fn job_state_from_job_outcome(job_outcome: JobOutcome) -> JobState {
+    match job_outcome {
+        JobOutcome::Completed => JobState::Completed,
+        JobOutcome::Failed => JobState::Failed,
+        JobOutcome::Cancelled => JobState::Cancelled,
+    }
+}
+
+/// Converts the given state of a finished job into its outcome, as persisted.
+fn job_outcome_from_job_state(job_state: JobState) -> JobOutcome {
+    match job_state {
+        JobState::Failed => JobOutcome::Failed,
+        JobState::Cancelled => JobOutcome::Cancelled,
+        _ => JobOutcome::Completed,
+    }
+}
+
+/// Serves the grid server's metrics in the Prometheus text format at `/metrics` on the given
+/// socket address.
+async fn serve_metrics(
+    grid_server: Arc<GridServerImpl>,
+    socket_address: SocketAddr,
+) -> Result<(), hyper::Error> {
+    let make_service = make_service_fn(move |_| {
+        let grid_server = Arc::clone(&grid_server);
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: hyper::Request<Body>| {
+                let grid_server = Arc::clone(&grid_server);
+
+                async move {
+                    match request.uri().path() {
+                        "/metrics" => hyper::Response::builder()
+                            .header(CONTENT_TYPE, "text/plain; version=0.0.4")
+                            .body(Body::from(grid_server.metrics_text())),
+                        _ => hyper::Response::builder()
+                            .status(StatusCode::NOT_FOUND)
+                            .body(Body::empty()),
+                    }
+                }
+            }))
+        }
+    });
+
+    hyper::Server::bind(&socket_address)
+        .serve(make_service)
+        .await
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+
+    // Get the given command line arguments.
+    let command_line_arguments: Vec<_> = args().skip(1).collect();
+
+    // Try to parse the configuration from the command line arguments.
+    let configuration = match Configuration::from_command_line_arguments(&command_line_arguments) {
+        Ok(configuration) => configuration,
+        Err(error_message) => {
+            error!("{error_message}");
+            error!("{USAGE}");
+            exit(-1);
+        }
+    };
+
+    let socket_address = configuration.socket_address;
+    let maybe_metrics_socket_address = configuration.metrics_socket_address;
+    let maybe_compression = configuration.compression;
+    let maybe_server_tls_config = configuration.tls_paths.server_tls_config()?;
+
+    // Try to open the trace file, if a path is given.
+    let trace_file = match &configuration.trace_file_path {
+        Some(trace_file_path) => {
+            info!("Tracing jobs in \"{}\"", trace_file_path.display());
+
+            Some(TraceFile::open(trace_file_path)?)
+        }
+        None => None,
+    };
+
+    let grid_server = match configuration.data_directory.clone() {
+        // A data directory is given.
+        Some(data_directory) => {
+            info!(
+                "Persisting jobs and results in \"{}\"",
+                data_directory.display()
+            );
+
+            // Try to open the store and restore the persisted state.
+            let (store, records) = Store::open(&data_directory)?;
+            let grid_server = GridServerImpl::new(configuration, Some(store), trace_file);
+            grid_server.restore(records);
+
+            // Compact the store, so that it does not grow across restarts.
+            if let Some(store) = &grid_server.store {
+                store.compact(&grid_server.compacted_records())?;
+            }
+
+            grid_server
+        }
+        // No data directory is given.
+        None => GridServerImpl::new(configuration, None, trace_file),
+    };
+    let grid_server = Arc::new(grid_server);
+
+    // Regularly queue the jobs again whose lease expired and drop the expired clients.
+    {
+        let grid_server = Arc::clone(&grid_server);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(1));
+
+            loop {
+                interval.tick().await;
+                grid_server.requeue_jobs_with_expired_lease();
+                grid_server.remove_expired_clients();
+            }
+        });
+    }
+
+    // Serve the metrics over HTTP, if an address is given.
+    if let Some(metrics_socket_address) = maybe_metrics_socket_address {
+        let grid_server = Arc::clone(&grid_server);
+
+        info!("Serving the metrics on \"{metrics_socket_address}\" ...");
+
+        tokio::spawn(async move {
+            if let Err(error) = serve_metrics(grid_server, metrics_socket_address).await {
+                error!("Failed to serve the metrics: {error}");
+            }
+        });
+    }
+
+    info!("Running the server on \"{}\" ...", socket_address);
+
+    // Register the signal handlers.
+    let _ = signal_hook::flag::register(libc::SIGINT, Arc::clone(&STOP_SERVER));
+    let _ = signal_hook::flag::register(libc::SIGTERM, Arc::clone(&STOP_SERVER));
+
+    // Stop serving once the server was requested to stop and is drained.
+    let drained = {
+        let grid_server = Arc::clone(&grid_server);
+
+        async move { grid_server.drain().await }
+    };
+
+    // Accept compressed requests of every encoding and compress the responses to the clients that
+    // accept the configured encoding.
+    let mut grid_server_server = GridServerServer::from_arc(Arc::clone(&grid_server))
+        .accept_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Zstd);
+
+    if let Some(compression) = maybe_compression {
+        info!("Compressing the responses with {compression}");
+
+        grid_server_server = grid_server_server.send_compressed(compression);
+    }
+
+    let mut server = Server::builder();
+
+    // Secure the connections with TLS, if a certificate is given.
+    if let Some(server_tls_config) = maybe_server_tls_config {
+        info!("Using TLS");
+
+        server = server.tls_config(server_tls_config)?;
+    }
+
+    server
+        .add_service(grid_server_server)
+        .serve_with_shutdown(socket_address, drained)
+        .await?;
+
+    grid_server.log_remaining_jobs_and_results();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The service of the jobs in the tests.
+    const SERVICE_ID: ServiceId = 1;
+
+    /// Returns the secret that the tests present to register with the given role.
+    fn role_secret(role: ClientRole) -> String {
+        match role {
+            ClientRole::Submitter => String::new(),
+            ClientRole::Worker => "worker secret".to_string(),
+            ClientRole::Controller => "controller secret".to_string(),
+        }
+    }
+
+    /// Returns the configuration with the given command line options, which are given after the
+    /// secrets of the worker and controller roles.
+    fn configuration(options: &[&str]) -> Configuration {
+        let role_secret_files = [ClientRole::Worker, ClientRole::Controller].map(|role| {
+            let role_secret_file = tempfile::NamedTempFile::new().unwrap();
+            std::fs::write(role_secret_file.path(), role_secret(role)).unwrap();
+            role_secret_file
+        });
+        let role_secret_options = [
+            format!("worker={}", role_secret_files[0].path().display()),
+            format!("controller={}", role_secret_files[1].path().display()),
+        ];
+
+        let command_line_arguments: Vec<String> = [
+            "127.0.0.1:0",
+            "--role-secret-file",
+            &role_secret_options[0],
+            "--role-secret-file",
+            &role_secret_options[1],
+        ]
+        .iter()
+        .chain(options)
+        .map(|argument| argument.to_string())
+        .collect();
+
+        Configuration::from_command_line_arguments(&command_line_arguments)
+            .expect("valid command line options")
+    }
+
+    /// Returns a grid server without a store, configured with the given command line options.
+    fn grid_server(options: &[&str]) -> GridServerImpl {
+        GridServerImpl::new(configuration(options), None, None)
+    }
+
+    /// Returns a grid server that is restored from the store in the given data directory and
+    /// compacts it, like when the grid server starts.
+    fn restored_grid_server(data_directory: &std::path::Path) -> GridServerImpl {
+        let (store, records) = Store::open(data_directory).expect("opened store");
+        let grid_server = GridServerImpl::new(configuration(&[]), Some(store), None);
+
+        grid_server.restore(records);
+
+        if let Some(store) = &grid_server.store {
+            store
+                .compact(&grid_server.compacted_records())
+                .expect("compacted store");
+        }
+
+        grid_server
+    }
+
+    /// Serves the given grid server on a free local port and returns its address.
+    async fn serve(grid_server: &Arc<GridServerImpl>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server_address = listener.local_addr().unwrap().to_string();
+        let grid_server_server = GridServerServer::from_arc(Arc::clone(grid_server));
+
+        tokio::spawn(
+            Server::builder()
+                .add_service(grid_server_server)
+                .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+        );
+
+        server_address
+    }
+
+    /// Connects a grid client with the given role to the grid server at the given address.
+    async fn connect(server_address: &str, role: ClientRole) -> grid_client::AsyncGridClient {
+        grid_client::connect_async_grid_client(
+            server_address,
+            "test".to_string(),
+            role,
+            Some(role_secret(role)),
+            None,
+            &Default::default(),
+        )
+        .await
+        .expect("connected client")
+    }
+
+    /// Registers a client with the given role and returns its session token.
+    async fn register(grid_server: &GridServerImpl, role: ClientRole) -> String {
+        grid_server
+            .client_register(Request::new(RequestFromClientRegister {
+                role: role.into(),
+                role_secret: role_secret(role),
+                ..Default::default()
+            }))
+            .await
+            .expect("registered client")
+            .into_inner()
+            .session_token
+    }
+
+    /// Submits a job with the given data and returns its ID.
+    async fn submit_job(
+        grid_server: &GridServerImpl,
+        session_token: &str,
+        job_data: &[u8],
+    ) -> JobId {
+        grid_server
+            .client_submit_job(Request::new(RequestFromClientJobSubmit {
+                session_token: session_token.to_string(),
+                service_id: SERVICE_ID,
+                service_version: 1,
+                job_data: job_data.to_vec(),
+                ..Default::default()
+            }))
+            .await
+            .expect("submitted job")
+            .into_inner()
+            .job_id
+            .expect("job ID")
+    }
+
+    /// Sends the given result, if any, and returns the next job, if there is one, without waiting.
+    async fn exchange(
+        grid_server: &GridServerImpl,
+        session_token: &str,
+        result: Option<grid_server_interface::Result>,
+    ) -> Option<Job> {
+        grid_server
+            .worker_server_exchange(Request::new(RequestFromWorkerExchange {
+                session_token: session_token.to_string(),
+                query_job_from_server: Some(JobQuery {
+                    service_id: SERVICE_ID,
+                    service_version: 1,
+                    job_count: 1,
+                }),
+                result_from_worker: result,
+                wait_milliseconds: 0,
+            }))
+            .await
+            .expect("exchanged jobs")
+            .into_inner()
+            .job
+    }
+
+    /// Returns the succeeded result of the given job with the given result data.
+    fn succeeded_result(job_id: JobId, result_data: Vec<u8>) -> grid_server_interface::Result {
+        grid_server_interface::Result {
+            job_id,
+            result_data,
+            status: ResultStatus::Succeeded.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the failed result of the given job.
+    fn failed_result(job_id: JobId) -> grid_server_interface::Result {
+        grid_server_interface::Result {
+            job_id,
+            status: ResultStatus::Failed.into(),
+            error_message: "Failed".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the state of the given job.
+    async fn job_state(
+        grid_server: &GridServerImpl,
+        session_token: &str,
+        job_id: JobId,
+    ) -> JobState {
+        grid_server
+            .client_get_job_status(Request::new(RequestFromClientJobStatusGet {
+                session_token: session_token.to_string(),
+                job_ids: vec![job_id],
+                ..Default::default()
+            }))
+            .await
+            .expect("job status")
+            .into_inner()
+            .job_status[0]
+            .state()
+    }
+
+    /// Lets every lease expire and queues the jobs again.
+    fn expire_leases(grid_server: &GridServerImpl) {
+        for lease in grid_server.leases_per_job_id.lock().unwrap().values_mut() {
+            lease.deadline = Instant::now();
+        }
+
+        grid_server.requeue_jobs_with_expired_lease();
+    }
+
+    #[tokio::test]
+    async fn expired_lease_queues_job_again_with_default_configuration() {
+        let grid_server = grid_server(&[]);
+        let client = register(&grid_server, ClientRole::Submitter).await;
+        let worker = register(&grid_server, ClientRole::Worker).await;
+        let job_id = submit_job(&grid_server, &client, b"job").await;
+
+        // Let the lease expire more often than a job may fail.
+        for _ in 0..3 {
+            let job = exchange(&grid_server, &worker, None).await.expect("job");
+            assert_eq!(job.job_id, job_id);
+            assert_eq!(
+                job_state(&grid_server, &client, job_id).await,
+                JobState::Dispatched
+            );
+
+            expire_leases(&grid_server);
+            assert_eq!(
+                job_state(&grid_server, &client, job_id).await,
+                JobState::Queued
+            );
+        }
+
+        assert!(grid_server
+            .dead_letters_per_job_id
+            .lock()
+            .unwrap()
+            .is_empty());
+        assert!(grid_server.results_per_client_id.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn only_the_lease_holder_extends_the_lease_and_a_late_result_is_accepted() {
+        let grid_server = grid_server(&[]);
+        let client = register(&grid_server, ClientRole::Submitter).await;
+        let worker = register(&grid_server, ClientRole::Worker).await;
+        let other_worker = register(&grid_server, ClientRole::Worker).await;
+        let job_id = submit_job(&grid_server, &client, b"job").await;
+
+        let extend_lease = |session_token: &str| {
+            grid_server.worker_extend_lease(Request::new(RequestFromWorkerLeaseExtend {
+                session_token: session_token.to_string(),
+                job_ids: vec![job_id],
+            }))
+        };
+        let lost_job_ids = |response: Result<Response<ResponseToWorkerLeaseExtend>, Status>| {
+            response.expect("extended lease").into_inner().lost_job_ids
+        };
+
+        exchange(&grid_server, &worker, None).await.expect("job");
+        let deadline = grid_server.leases_per_job_id.lock().unwrap()[&job_id].deadline;
+
+        assert!(lost_job_ids(extend_lease(&worker).await).is_empty());
+        assert!(grid_server.leases_per_job_id.lock().unwrap()[&job_id].deadline >= deadline);
+        assert_eq!(lost_job_ids(extend_lease(&other_worker).await), [job_id]);
+
+        // The lease is lost once it expired.
+        expire_leases(&grid_server);
+        assert_eq!(lost_job_ids(extend_lease(&worker).await), [job_id]);
+
+        // The result of the worker is still accepted and the queued job is not dispatched again.
+        exchange(
+            &grid_server,
+            &worker,
+            Some(succeeded_result(job_id, b"result".to_vec())),
+        )
+        .await;
+        assert_eq!(
+            job_state(&grid_server, &client, job_id).await,
+            JobState::Completed
+        );
+        assert!(exchange(&grid_server, &other_worker, None).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn failed_job_is_retried_and_then_moved_to_dead_letter_queue() {
+        let grid_server = grid_server(&["--max-attempts", "2"]);
+        let client = register(&grid_server, ClientRole::Submitter).await;
+        let worker = register(&grid_server, ClientRole::Worker).await;
+        let job_id = submit_job(&grid_server, &client, b"job").await;
+
+        // The first failure queues the job again, an expired lease in between is not counted.
+        exchange(&grid_server, &worker, None).await.expect("job");
+        let job = exchange(&grid_server, &worker, Some(failed_result(job_id))).await;
+        assert_eq!(job.map(|job| job.job_id), Some(job_id));
+        expire_leases(&grid_server);
+        exchange(&grid_server, &worker, None).await.expect("job");
+
+        // The second failure moves the job to the dead-letter queue and fails it.
+        let job = exchange(&grid_server, &worker, Some(failed_result(job_id))).await;
+        assert!(job.is_none());
+        assert_eq!(
+            job_state(&grid_server, &client, job_id).await,
+            JobState::Failed
+        );
+
+        let dead_letters = grid_server.dead_letters_per_job_id.lock().unwrap();
+        assert_eq!(dead_letters[&job_id].attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn failure_from_worker_without_lease_is_ignored() {
+        let grid_server = grid_server(&[]);
+        let client = register(&grid_server, ClientRole::Submitter).await;
+        let worker = register(&grid_server, ClientRole::Worker).await;
+        let other_worker = register(&grid_server, ClientRole::Worker).await;
+        let job_id = submit_job(&grid_server, &client, b"job").await;
+
+        exchange(&grid_server, &worker, None).await.expect("job");
+        exchange(&grid_server, &other_worker, Some(failed_result(job_id))).await;
+
+        assert_eq!(
+            job_state(&grid_server, &client, job_id).await,
+            JobState::Dispatched
+        );
+        assert!(grid_server
+            .dead_letters_per_job_id
+            .lock()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn streamed_results_are_sent_in_chunks() {
+        use tokio_stream::StreamExt;
+
+        let grid_server = grid_server(&[]);
+        let client = register(&grid_server, ClientRole::Submitter).await;
+        let worker = register(&grid_server, ClientRole::Worker).await;
+        let job_ids = [
+            submit_job(&grid_server, &client, b"large").await,
+            submit_job(&grid_server, &client, b"small").await,
+        ];
+
+        // The first result arrives before the result stream is connected, the second after.
+        exchange(&grid_server, &worker, None).await.expect("job");
+        let result_data = vec![1; DATA_CHUNK_SIZE + 1];
+        exchange(
+            &grid_server,
+            &worker,
+            Some(succeeded_result(job_ids[0], result_data.clone())),
+        )
+        .await
+        .expect("job");
+
+        let mut result_stream = grid_server
+            .client_stream_results(Request::new(RequestFromClientResultStream {
+                session_token: client.clone(),
+            }))
+            .await
+            .expect("result stream")
+            .into_inner();
+
+        exchange(
+            &grid_server,
+            &worker,
+            Some(succeeded_result(job_ids[1], b"result".to_vec())),
+        )
+        .await;
+
+        let mut chunks = vec![];
+
+        for _ in 0..3 {
+            chunks.push(result_stream.next().await.expect("chunk").expect("chunk"));
+        }
+
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| (
+                    chunk.job_id,
+                    chunk.result_data.len(),
+                    chunk.result_data_size
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (job_ids[0], DATA_CHUNK_SIZE, result_data.len() as u64),
+                (job_ids[0], 1, result_data.len() as u64),
+                (job_ids[1], 6, 6),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn client_result_stream_yields_whole_results() {
+        use tokio_stream::StreamExt;
+
+        let grid_server = Arc::new(grid_server(&[]));
+        let server_address = serve(&grid_server).await;
+        let mut client = connect(&server_address, ClientRole::Submitter).await;
+        let worker = register(&grid_server, ClientRole::Worker).await;
+
+        let mut job_ids = vec![];
+
+        for job_data in [b"large", b"small"] {
+            let job_id = client
+                .client_submit_job(SERVICE_ID, 1, job_data.to_vec(), None, Labels::new())
+                .await
+                .expect("submitted job")
+                .into_inner()
+                .job_id
+                .expect("job ID");
+            job_ids.push(job_id);
+        }
+
+        let large_result_data = vec![1; 2 * DATA_CHUNK_SIZE + 1];
+        exchange(&grid_server, &worker, None).await.expect("job");
+        exchange(
+            &grid_server,
+            &worker,
+            Some(succeeded_result(job_ids[0], large_result_data.clone())),
+        )
+        .await
+        .expect("job");
+        exchange(
+            &grid_server,
+            &worker,
+            Some(succeeded_result(job_ids[1], b"result".to_vec())),
+        )
+        .await;
+
+        let mut result_stream = client
+            .client_stream_results()
+            .await
+            .expect("result stream")
+            .into_inner();
+
+        let large_result = result_stream.next().await.expect("result").expect("result");
+        assert_eq!(large_result.job_id, job_ids[0]);
+        assert_eq!(large_result.result_data, large_result_data);
+
+        let small_result = result_stream.next().await.expect("result").expect("result");
+        assert_eq!(small_result.job_id, job_ids[1]);
+        assert_eq!(small_result.result_data, b"result");
+    }
+
+    #[tokio::test]
+    async fn large_jobs_are_streamed_together_with_the_other_jobs() {
+        let grid_server = Arc::new(grid_server(&[]));
+        let server_address = serve(&grid_server).await;
+        let mut client = connect(&server_address, ClientRole::Submitter).await;
+        let worker = register(&grid_server, ClientRole::Worker).await;
+
+        let job_data = vec![
+            b"small".to_vec(),
+            vec![1; 2 * DATA_CHUNK_SIZE + 1],
+            b"small".to_vec(),
+            vec![2; DATA_CHUNK_SIZE + 1],
+        ];
+        let job_ids = client
+            .client_submit_jobs(SERVICE_ID, 1, job_data.clone(), None, Labels::new())
+            .await
+            .expect("submitted jobs")
+            .into_inner()
+            .job_ids;
+        assert_eq!(job_ids.len(), job_data.len());
+
+        // The jobs are queued in order with their whole job data.
+        for (job_id, job_data) in job_ids.into_iter().zip(job_data) {
+            let job = exchange(&grid_server, &worker, None).await.expect("job");
+            assert_eq!(job.job_id, job_id);
+            assert_eq!(
+                job.job_data.len().max(job.job_data_size as usize),
+                job_data.len()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn downloaded_results_are_kept_until_acknowledged() {
+        let grid_server = Arc::new(grid_server(&[]));
+        let server_address = serve(&grid_server).await;
+        let mut client = connect(&server_address, ClientRole::Submitter).await;
+        let worker = register(&grid_server, ClientRole::Worker).await;
+
+        let job_id = client
+            .client_submit_job(SERVICE_ID, 1, b"job".to_vec(), None, Labels::new())
+            .await
+            .expect("submitted job")
+            .into_inner()
+            .job_id
+            .expect("job ID");
+        exchange(&grid_server, &worker, None).await.expect("job");
+        let result_data = vec![1; DATA_CHUNK_SIZE + 1];
+        exchange(
+            &grid_server,
+            &worker,
+            Some(succeeded_result(job_id, result_data.clone())),
+        )
+        .await;
+
+        // A download that breaks off does not lose the result.
+        let mut result_stream = client
+            .client_download_results(Labels::new())
+            .await
+            .expect("result stream")
+            .into_inner();
+        let chunk = result_stream
+            .message()
+            .await
+            .expect("chunk")
+            .expect("chunk");
+        assert_eq!(chunk.job_id, job_id);
+        drop(result_stream);
+
+        let results = client
+            .client_fetch_results(Labels::new())
+            .await
+            .expect("results")
+            .into_inner()
+            .results;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].result_data, result_data);
+
+        // The fetched result was acknowledged.
+        assert!(grid_server.results_per_client_id.lock().unwrap().is_empty());
+    }
+
+    /// Returns the status with which the grid server at the given address rejects a stream that
+    /// starts with the given jobs and does not end.
+    async fn rejection_of_endless_job_stream(
+        server_address: &str,
+        session_token: &str,
+        job_data: Vec<Vec<u8>>,
+    ) -> Status {
+        let mut grid_server_client =
+            grid_server_interface::GridServerClient::connect(format!("http://{server_address}"))
+                .await
+                .unwrap();
+        let request = RequestFromClientJobsSubmit {
+            session_token: session_token.to_string(),
+            service_id: SERVICE_ID,
+            job_data,
+            ..Default::default()
+        };
+        let request_stream =
+            tokio_stream::StreamExt::chain(tokio_stream::once(request), tokio_stream::pending());
+
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            grid_server_client.client_stream_jobs(request_stream),
+        )
+        .await
+        .expect("rejected before the end of the stream")
+        .unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn job_stream_is_rejected_before_it_ends() {
+        let grid_server = Arc::new(grid_server(&[
+            "--client-limit",
+            "queued-jobs=2",
+            "--max-data-size",
+            "10",
+        ]));
+        let server_address = serve(&grid_server).await;
+        let client = register(&grid_server, ClientRole::Submitter).await;
+
+        let status = rejection_of_endless_job_stream(&server_address, "unknown", vec![]).await;
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+        let job_data = vec![b"job".to_vec(); 3];
+        let status = rejection_of_endless_job_stream(&server_address, &client, job_data).await;
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+
+        let job_data = vec![vec![1; 11]];
+        let status = rejection_of_endless_job_stream(&server_address, &client, job_data).await;
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+
+        assert!(grid_server.client_id_per_job_id.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn maximum_data_size_limits_each_job_of_a_stream() {
+        let grid_server = Arc::new(grid_server(&["--max-data-size", "10"]));
+        let server_address = serve(&grid_server).await;
+        let mut client = connect(&server_address, ClientRole::Submitter).await;
+
+        // The jobs are larger than the maximum data size together, but not on their own.
+        let job_data = vec![vec![1; 10]; 3];
+        let job_ids = client
+            .client_submit_jobs(SERVICE_ID, 1, job_data, None, Labels::new())
+            .await
+            .expect("submitted jobs")
+            .into_inner()
+            .job_ids;
+        assert_eq!(job_ids.len(), 3);
+    }
+
+    /// Lets every client be idle for the given time and drops the expired clients.
+    fn idle_clients(grid_server: &GridServerImpl, idle_time: chrono::Duration) {
+        for client_information in grid_server
+            .client_information_per_client_id
+            .lock()
+            .unwrap()
+            .values_mut()
+        {
+            client_information.last_access = Utc::now() - idle_time;
+        }
+
+        grid_server.remove_expired_clients();
+    }
+
+    #[tokio::test]
+    async fn idle_clients_are_kept_without_session_timeout() {
+        let grid_server = grid_server(&[]);
+        register(&grid_server, ClientRole::Submitter).await;
+
+        idle_clients(&grid_server, chrono::Duration::days(30));
+
+        assert_eq!(
+            grid_server
+                .client_information_per_client_id
+                .lock()
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn only_idle_clients_without_jobs_and_results_expire() {
+        let grid_server = grid_server(&["--session-timeout", "60"]);
+        let client_with_job = register(&grid_server, ClientRole::Submitter).await;
+        let client_with_result = register(&grid_server, ClientRole::Submitter).await;
+        let idle_client = register(&grid_server, ClientRole::Submitter).await;
+        let worker = register(&grid_server, ClientRole::Worker).await;
+
+        let open_job_id = submit_job(&grid_server, &client_with_job, b"open").await;
+        let finished_job_id = submit_job(&grid_server, &client_with_result, b"finished").await;
+        exchange(&grid_server, &worker, None).await.expect("job");
+        exchange(&grid_server, &worker, None).await.expect("job");
+        exchange(
+            &grid_server,
+            &worker,
+            Some(succeeded_result(finished_job_id, vec![])),
+        )
+        .await;
+
+        idle_clients(&grid_server, chrono::Duration::hours(1));
+
+        // The clients with a job or a result are kept with them, the others expired.
+        assert_eq!(
+            job_state(&grid_server, &client_with_job, open_job_id).await,
+            JobState::Dispatched
+        );
+        assert_eq!(
+            job_state(&grid_server, &client_with_result, finished_job_id).await,
+            JobState::Completed
+        );
+        for session_token in [idle_client, worker] {
+            assert!(!grid_server
+                .client_id_per_session_token
+                .lock()
+                .unwrap()
+                .contains_key(&session_token));
+        }
+    }
+
+    #[tokio::test]
+    async fn fetched_jobs_are_forgotten_beyond_the_finished_job_retention() {
+        let grid_server = grid_server(&["--finished-job-retention", "1"]);
+        let submitter = register(&grid_server, ClientRole::Submitter).await;
+        let worker = register(&grid_server, ClientRole::Worker).await;
+
+        let first_job_id = submit_job(&grid_server, &submitter, b"first").await;
+        let second_job_id = submit_job(&grid_server, &submitter, b"second").await;
+        exchange(&grid_server, &worker, None)
+            .await
+            .expect("first job");
+        exchange(
+            &grid_server,
+            &worker,
+            Some(succeeded_result(first_job_id, vec![1])),
+        )
+        .await
+        .expect("second job");
+        exchange(
+            &grid_server,
+            &worker,
+            Some(succeeded_result(second_job_id, vec![2])),
+        )
+        .await;
+
+        // The status of finished jobs is kept until their results are fetched.
+        assert_eq!(
+            job_state(&grid_server, &submitter, first_job_id).await,
+            JobState::Completed
+        );
+
+        let fetch_results = || {
+            grid_server.client_fetch_results(Request::new(RequestFromClientResultFetch {
+                session_token: submitter.clone(),
+                ..Default::default()
+            }))
+        };
+        assert_eq!(fetch_results().await.unwrap().into_inner().results.len(), 2);
+
+        // Only the last fetched job is retained.
+        assert_eq!(
+            job_state(&grid_server, &submitter, first_job_id).await,
+            JobState::Unknown
+        );
+        assert_eq!(
+            job_state(&grid_server, &submitter, second_job_id).await,
+            JobState::Completed
+        );
+        assert_eq!(
+            grid_server.finished_jobs_per_job_id.lock().unwrap().len(),
+            1
+        );
+        assert_eq!(grid_server.job_traces_per_job_id.lock().unwrap().len(), 1);
+
+        // Cancelled jobs are retained the same way.
+        let third_job_id = submit_job(&grid_server, &submitter, b"third").await;
+        grid_server
+            .client_cancel_job(Request::new(RequestFromClientJobCancel {
+                session_token: submitter.clone(),
+                job_ids: vec![third_job_id],
+                ..Default::default()
+            }))
+            .await
+            .expect("cancelled job");
+        assert_eq!(
+            job_state(&grid_server, &submitter, second_job_id).await,
+            JobState::Unknown
+        );
+        assert_eq!(
+            job_state(&grid_server, &submitter, third_job_id).await,
+            JobState::Cancelled
+        );
+    }
+
+    #[tokio::test]
+    async fn cancelled_jobs_are_told_after_a_rejected_exchange() {
+        let grid_server = grid_server(&["--max-data-size", "10"]);
+        let submitter = register(&grid_server, ClientRole::Submitter).await;
+        let worker = register(&grid_server, ClientRole::Worker).await;
+
+        let job_id = submit_job(&grid_server, &submitter, b"job").await;
+        exchange(&grid_server, &worker, None).await.expect("job");
+        grid_server
+            .client_cancel_job(Request::new(RequestFromClientJobCancel {
+                session_token: submitter.clone(),
+                job_ids: vec![job_id],
+                ..Default::default()
+            }))
+            .await
+            .expect("cancelled job");
+
+        let exchange_with_result = |result| {
+            grid_server.worker_server_exchange(Request::new(RequestFromWorkerExchange {
+                session_token: worker.clone(),
+                result_from_worker: result,
+                ..Default::default()
+            }))
+        };
+
+        // The result is too large, so the exchange is rejected.
+        let status = exchange_with_result(Some(succeeded_result(job_id, vec![1; 11])))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+
+        // The worker is still told about the cancelled job.
+        let response = exchange_with_result(None).await.expect("exchanged jobs");
+        assert_eq!(response.get_ref().cancelled_job_ids, vec![job_id]);
+    }
+
+    #[tokio::test]
+    async fn jobs_beyond_the_in_flight_limit_stay_queued() {
+        let grid_server = grid_server(&["--client-limit", "in-flight-jobs=1"]);
+        let submitter = register(&grid_server, ClientRole::Submitter).await;
+        let other_submitter = register(&grid_server, ClientRole::Submitter).await;
+        let worker = register(&grid_server, ClientRole::Worker).await;
+
+        let first_job_id = submit_job(&grid_server, &submitter, b"first").await;
+        let second_job_id = submit_job(&grid_server, &submitter, b"second").await;
+        let other_job_id = submit_job(&grid_server, &other_submitter, b"other").await;
+
+        // The second job of the client waits for the first, the job of the other client does not.
+        assert_eq!(
+            exchange(&grid_server, &worker, None).await.unwrap().job_id,
+            first_job_id
+        );
+        assert_eq!(
+            exchange(&grid_server, &worker, None).await.unwrap().job_id,
+            other_job_id
+        );
+        assert!(exchange(&grid_server, &worker, None).await.is_none());
+        assert_eq!(
+            job_state(&grid_server, &submitter, second_job_id).await,
+            JobState::Queued
+        );
+
+        // The second job is dispatched once the first is finished.
+        assert_eq!(
+            exchange(
+                &grid_server,
+                &worker,
+                Some(succeeded_result(first_job_id, vec![]))
+            )
+            .await
+            .unwrap()
+            .job_id,
+            second_job_id
+        );
+    }
+
+    #[tokio::test]
+    async fn jobs_submitted_at_the_same_time_do_not_exceed_the_queue_limit() {
+        let grid_server = Arc::new(grid_server(&["--user-limit", "queued-jobs=10"]));
+        let submitter = register(&grid_server, ClientRole::Submitter).await;
+
+        let submissions: Vec<_> = (0..20)
+            .map(|_| {
+                let grid_server = Arc::clone(&grid_server);
+                let submitter = submitter.clone();
+
+                tokio::spawn(async move {
+                    grid_server
+                        .client_submit_job(Request::new(RequestFromClientJobSubmit {
+                            session_token: submitter,
+                            service_id: SERVICE_ID,
+                            service_version: 1,
+                            job_data: b"job".to_vec(),
+                            ..Default::default()
+                        }))
+                        .await
+                        .is_ok()
+                })
+            })
+            .collect();
+
+        let mut accepted_job_count = 0;
+
+        for submission in submissions {
+            if submission.await.unwrap() {
+                accepted_job_count += 1;
+            }
+        }
+
+        assert_eq!(accepted_job_count, 10);
+    }
+
+    #[tokio::test]
+    async fn service_version_requirements_are_resolved_when_jobs_are_submitted() {
+        let grid_server = grid_server(&[
+            "--service-alias",
+            "1/stable=>=41",
+            "--service-alias",
+            "1/newest=latest",
+        ]);
+        let service_versions = |service_version_requirement: &str| {
+            grid_server.service_versions(SERVICE_ID, service_version_requirement.parse().unwrap())
+        };
+
+        assert_eq!(
+            service_versions("41").unwrap(),
+            ServiceVersions::exactly(41)
+        );
+        assert_eq!(
+            service_versions(">=41").unwrap(),
+            ServiceVersions::at_least(41)
+        );
+        assert_eq!(
+            service_versions("stable").unwrap(),
+            ServiceVersions::at_least(41)
+        );
+        assert_eq!(
+            service_versions("unstable").unwrap_err().code(),
+            tonic::Code::InvalidArgument
+        );
+
+        // The latest version is unknown until a grid worker asks for jobs.
+        assert_eq!(
+            service_versions("latest").unwrap_err().code(),
+            tonic::Code::FailedPrecondition
+        );
+        assert_eq!(
+            service_versions("newest").unwrap_err().code(),
+            tonic::Code::FailedPrecondition
+        );
+
+        let worker = register(&grid_server, ClientRole::Worker).await;
+        exchange(&grid_server, &worker, None).await;
+
+        assert_eq!(
+            service_versions("latest").unwrap(),
+            ServiceVersions::at_least(1)
+        );
+        assert_eq!(
+            service_versions("newest").unwrap(),
+            ServiceVersions::at_least(1)
+        );
+    }
+
+    #[tokio::test]
+    async fn jobs_and_results_are_restored_from_the_store() {
+        let data_directory = tempfile::tempdir().unwrap();
+
+        let (submitter, completed_job_id, dispatched_job_id, queued_job_id, failed_job_id) = {
+            let grid_server = restored_grid_server(data_directory.path());
+            let submitter = register(&grid_server, ClientRole::Submitter).await;
+            let worker = register(&grid_server, ClientRole::Worker).await;
+
+            let completed_job_id = submit_job(&grid_server, &submitter, b"completed").await;
+            let failed_job_id = submit_job(&grid_server, &submitter, b"failed").await;
+            let dispatched_job_id = submit_job(&grid_server, &submitter, b"dispatched").await;
+            let queued_job_id = submit_job(&grid_server, &submitter, b"queued").await;
+
+            exchange(&grid_server, &worker, None).await.expect("job");
+            exchange(
+                &grid_server,
+                &worker,
+                Some(succeeded_result(completed_job_id, b"result".to_vec())),
+            )
+            .await
+            .expect("job");
+            exchange(&grid_server, &worker, Some(failed_result(failed_job_id)))
+                .await
+                .expect("job");
+
+            (
+                submitter,
+                completed_job_id,
+                dispatched_job_id,
+                queued_job_id,
+                failed_job_id,
+            )
+        };
+
+        // Restore twice, from the log and from the compacted log.
+        for _ in 0..2 {
+            let grid_server = restored_grid_server(data_directory.path());
+
+            assert_eq!(
+                job_state(&grid_server, &submitter, completed_job_id).await,
+                JobState::Completed
+            );
+            assert_eq!(
+                job_state(&grid_server, &submitter, failed_job_id).await,
+                JobState::Failed
+            );
+            // The job that was dispatched without a result is queued again, in its order.
+            assert_eq!(
+                job_state(&grid_server, &submitter, dispatched_job_id).await,
+                JobState::Queued
+            );
+            assert_eq!(
+                job_state(&grid_server, &submitter, queued_job_id).await,
+                JobState::Queued
+            );
+            assert_eq!(
+                grid_server
+                    .dead_letters_per_job_id
+                    .lock()
+                    .unwrap()
+                    .keys()
+                    .collect::<Vec<_>>(),
+                [&failed_job_id]
+            );
+            assert_eq!(
+                grid_server
+                    .results_per_client_id
+                    .lock()
+                    .unwrap()
+                    .values()
+                    .map(Vec::len)
+                    .sum::<usize>(),
+                2
+            );
+        }
+
+        {
+            let grid_server = restored_grid_server(data_directory.path());
+
+            let results = grid_server
+                .client_fetch_results(Request::new(RequestFromClientResultFetch {
+                    session_token: submitter.clone(),
+                    ..Default::default()
+                }))
+                .await
+                .expect("fetched results")
+                .into_inner()
+                .results;
+            assert_eq!(results.len(), 2);
+        }
+
+        // Fetched results are not restored, and the job IDs are not handed out again.
+        let grid_server = restored_grid_server(data_directory.path());
+        let worker = register(&grid_server, ClientRole::Worker).await;
+
+        assert!(grid_server.results_per_client_id.lock().unwrap().is_empty());
+        assert_eq!(
+            exchange(&grid_server, &worker, None).await.unwrap().job_id,
+            dispatched_job_id
+        );
+        assert!(submit_job(&grid_server, &submitter, b"new").await > queued_job_id);
+    }
+
+    #[tokio::test]
+    async fn role_with_secret_is_only_granted_with_the_secret() {
+        let role_secret_file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(role_secret_file.path(), "secret\n").unwrap();
+        let role_secret_option = format!("worker={}", role_secret_file.path().display());
+        let grid_server = grid_server(&["--role-secret-file", &role_secret_option]);
+
+        let register_worker = |role_secret: &str| {
+            grid_server.client_register(Request::new(RequestFromClientRegister {
+                role: ClientRole::Worker.into(),
+                role_secret: role_secret.to_string(),
+                ..Default::default()
+            }))
+        };
+
+        assert_eq!(
+            register_worker("").await.unwrap_err().code(),
+            tonic::Code::PermissionDenied
+        );
+        assert_eq!(
+            register_worker("wrong").await.unwrap_err().code(),
+            tonic::Code::PermissionDenied
+        );
+        assert!(register_worker("secret").await.is_ok());
+
+        // The roles without a secret are granted to every client.
+        register(&grid_server, ClientRole::Submitter).await;
+    }
+
+    #[test]
+    fn worker_and_controller_roles_require_a_secret() {
+        let role_secret_file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(role_secret_file.path(), "secret\n").unwrap();
+        let role_secret_option = |role| format!("{role}={}", role_secret_file.path().display());
+        let from_options = |options: &[&str]| {
+            let command_line_arguments: Vec<String> = ["127.0.0.1:0"]
+                .iter()
+                .chain(options)
+                .map(|argument| argument.to_string())
+                .collect();
+
+            Configuration::from_command_line_arguments(&command_line_arguments)
+        };
+
+        let worker_option = role_secret_option("worker");
+        let controller_option = role_secret_option("controller");
+
+        assert!(from_options(&[]).is_err());
+        assert!(from_options(&["--role-secret-file", &worker_option]).is_err());
+        assert!(from_options(&["--role-secret-file", &controller_option]).is_err());
+        assert!(from_options(&[
+            "--role-secret-file",
+            &worker_option,
+            "--role-secret-file",
+            &controller_option
+        ])
+        .is_ok());
+    }
 }
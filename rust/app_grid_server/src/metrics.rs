@@ -0,0 +1,267 @@
+use crate::service_versions::ServiceVersions;
+use grid_server_interface::{ServiceId, ServiceVersion};
+use prometheus::core::Collector;
+use prometheus::{
+    exponential_buckets, Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::time::Duration;
+
+/// The labels of the metrics per service.
+const SERVICE_LABELS: &[&str] = &["service_id", "service_version"];
+
+/// The labels of the metrics per service of queued jobs. The service version is the oldest version
+/// that may process the jobs, like for the other metrics, and the service versions are the range
+/// of versions that may process them, e.g. ">=41".
+const QUEUE_LABELS: &[&str] = &["service_id", "service_version", "service_versions"];
+
+/// The metrics of the grid server, in the Prometheus format.
+pub struct Metrics {
+    /// The jobs dispatched to grid workers, per service. A job that is attempted several times is
+    /// counted several times.
+    jobs_dispatched: IntCounterVec,
+    /// The jobs that got a successful result, per service.
+    jobs_completed: IntCounterVec,
+    /// The jobs that failed too often and were moved to the dead-letter queue, per service.
+    jobs_failed: IntCounterVec,
+    /// The jobs submitted by clients, per service and service versions.
+    jobs_submitted: IntCounterVec,
+    /// The results that were not fetched by their clients yet.
+    pending_results: IntGauge,
+    /// The time from dispatching a job to receiving its result, per service.
+    processing_seconds: HistogramVec,
+    /// The number of queued jobs, per service and service versions.
+    queue_depth: IntGaugeVec,
+    /// The time a job waited in the queue before it was dispatched, per service.
+    queue_wait_seconds: HistogramVec,
+    /// The clients registered with the grid server.
+    registered_clients: IntGauge,
+    registry: Registry,
+}
+
+impl Metrics {
+    /// Creates the metrics, all starting at zero.
+    pub fn new() -> Self {
+        // The buckets from 1 millisecond to about 9 hours.
+        let buckets = exponential_buckets(0.001, 4.0, 13).unwrap();
+
+        let metrics = Metrics {
+            jobs_dispatched: IntCounterVec::new(
+                Opts::new(
+                    "grid_jobs_dispatched_total",
+                    "Jobs dispatched to grid workers",
+                ),
+                SERVICE_LABELS,
+            )
+            .unwrap(),
+            jobs_completed: IntCounterVec::new(
+                Opts::new("grid_jobs_completed_total", "Jobs with a successful result"),
+                SERVICE_LABELS,
+            )
+            .unwrap(),
+            jobs_failed: IntCounterVec::new(
+                Opts::new(
+                    "grid_jobs_failed_total",
+                    "Jobs moved to the dead-letter queue",
+                ),
+                SERVICE_LABELS,
+            )
+            .unwrap(),
+            jobs_submitted: IntCounterVec::new(
+                Opts::new("grid_jobs_submitted_total", "Jobs submitted by clients"),
+                QUEUE_LABELS,
+            )
+            .unwrap(),
+            pending_results: IntGauge::new(
+                "grid_pending_results",
+                "Results not fetched by their clients yet",
+            )
+            .unwrap(),
+            processing_seconds: HistogramVec::new(
+                HistogramOpts::new(
+                    "grid_job_processing_seconds",
+                    "Time from dispatching a job to receiving its result",
+                )
+                .buckets(buckets.clone()),
+                SERVICE_LABELS,
+            )
+            .unwrap(),
+            queue_depth: IntGaugeVec::new(
+                Opts::new("grid_queue_depth", "Queued jobs"),
+                QUEUE_LABELS,
+            )
+            .unwrap(),
+            queue_wait_seconds: HistogramVec::new(
+                HistogramOpts::new(
+                    "grid_job_queue_wait_seconds",
+                    "Time a job waited in the queue before it was dispatched",
+                )
+                .buckets(buckets),
+                SERVICE_LABELS,
+            )
+            .unwrap(),
+            registered_clients: IntGauge::new("grid_registered_clients", "Registered clients")
+                .unwrap(),
+            registry: Registry::new(),
+        };
+
+        // Register the metrics, so that they are encoded.
+        let collectors: Vec<Box<dyn Collector>> = vec![
+            Box::new(metrics.jobs_dispatched.clone()),
+            Box::new(metrics.jobs_completed.clone()),
+            Box::new(metrics.jobs_failed.clone()),
+            Box::new(metrics.jobs_submitted.clone()),
+            Box::new(metrics.pending_results.clone()),
+            Box::new(metrics.processing_seconds.clone()),
+            Box::new(metrics.queue_depth.clone()),
+            Box::new(metrics.queue_wait_seconds.clone()),
+            Box::new(metrics.registered_clients.clone()),
+        ];
+
+        for collector in collectors {
+            metrics.registry.register(collector).unwrap();
+        }
+
+        metrics
+    }
+
+    /// Counts the given number of jobs submitted for the given service.
+    pub fn jobs_submitted(
+        &self,
+        service_id: ServiceId,
+        service_versions: ServiceVersions,
+        job_count: u64,
+    ) {
+        let [service_id, service_version, service_versions] =
+            queue_labels(service_id, service_versions);
+
+        self.jobs_submitted
+            .with_label_values(&[&service_id, &service_version, &service_versions])
+            .inc_by(job_count);
+    }
+
+    /// Counts a job of the given service dispatched after waiting in the queue for the given time.
+    pub fn job_dispatched(
+        &self,
+        service_id: ServiceId,
+        service_version: ServiceVersion,
+        queue_wait: Duration,
+    ) {
+        let [service_id, service_version] = service_labels(service_id, service_version);
+
+        self.jobs_dispatched
+            .with_label_values(&[&service_id, &service_version])
+            .inc();
+        self.queue_wait_seconds
+            .with_label_values(&[&service_id, &service_version])
+            .observe(queue_wait.as_secs_f64());
+    }
+
+    /// Observes the time from dispatching a job of the given service to receiving its result.
+    pub fn job_processed(
+        &self,
+        service_id: ServiceId,
+        service_version: ServiceVersion,
+        processing_time: Duration,
+    ) {
+        let [service_id, service_version] = service_labels(service_id, service_version);
+
+        self.processing_seconds
+            .with_label_values(&[&service_id, &service_version])
+            .observe(processing_time.as_secs_f64());
+    }
+
+    /// Counts a job of the given service that got a successful result.
+    pub fn job_completed(&self, service_id: ServiceId, service_version: ServiceVersion) {
+        let [service_id, service_version] = service_labels(service_id, service_version);
+
+        self.jobs_completed
+            .with_label_values(&[&service_id, &service_version])
+            .inc();
+    }
+
+    /// Counts a job of the given service that was moved to the dead-letter queue.
+    pub fn job_failed(&self, service_id: ServiceId, service_version: ServiceVersion) {
+        let [service_id, service_version] = service_labels(service_id, service_version);
+
+        self.jobs_failed
+            .with_label_values(&[&service_id, &service_version])
+            .inc();
+    }
+
+    /// Sets the gauges to the given queue depths per service, pending results and registered
+    /// clients.
+    pub fn set_gauges(
+        &self,
+        queue_depths: &[(ServiceId, ServiceVersions, usize)],
+        pending_results: usize,
+        registered_clients: usize,
+    ) {
+        // Drop the queues that vanished.
+        self.queue_depth.reset();
+
+        for (service_id, service_versions, queue_depth) in queue_depths {
+            let [service_id, service_version, service_versions] =
+                queue_labels(*service_id, *service_versions);
+
+            self.queue_depth
+                .with_label_values(&[&service_id, &service_version, &service_versions])
+                .set(*queue_depth as i64);
+        }
+
+        self.pending_results.set(pending_results as i64);
+        self.registered_clients.set(registered_clients as i64);
+    }
+
+    /// Returns the metrics in the Prometheus text format.
+    pub fn encode(&self) -> String {
+        let mut buffer = vec![];
+
+        // Encoding into a vector can not fail.
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .unwrap();
+
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+/// Returns the label values of the metrics for the given service.
+fn service_labels(service_id: ServiceId, service_version: ServiceVersion) -> [String; 2] {
+    [service_id.to_string(), service_version.to_string()]
+}
+
+/// Returns the label values of the metrics for the queued jobs of the given service, which may be
+/// processed by the given service versions.
+fn queue_labels(service_id: ServiceId, service_versions: ServiceVersions) -> [String; 3] {
+    [
+        service_id.to_string(),
+        service_versions.oldest.to_string(),
+        service_versions.to_string(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queued_jobs_are_labelled_with_their_oldest_version_and_their_range() {
+        let metrics = Metrics::new();
+
+        metrics.jobs_submitted(1, ServiceVersions::at_least(41), 2);
+        metrics.job_dispatched(1, 42, Duration::ZERO);
+        metrics.set_gauges(&[(1, ServiceVersions::exactly(41), 3)], 0, 0);
+
+        let encoded_metrics = metrics.encode();
+
+        assert!(encoded_metrics.contains(
+            r#"grid_jobs_submitted_total{service_id="1",service_version="41",service_versions=">=41"} 2"#
+        ));
+        assert!(encoded_metrics
+            .contains(r#"grid_jobs_dispatched_total{service_id="1",service_version="42"} 1"#));
+        assert!(encoded_metrics.contains(
+            r#"grid_queue_depth{service_id="1",service_version="41",service_versions="41"} 3"#
+        ));
+    }
+}
@@ -0,0 +1,186 @@
+pub mod grid_server_persistence {
+    // Include the generated rust module.
+    tonic::include_proto!("grid_server_persistence");
+}
+
+pub use grid_server_persistence::record::Record as RecordKind;
+pub use grid_server_persistence::*;
+
+use prost::Message;
+use std::fs::{rename, File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The name of the write-ahead log file within the data directory.
+const LOG_FILE_NAME: &str = "grid_server.wal";
+
+/// An append-only write-ahead log of the grid server's jobs and results.
+pub struct Store {
+    /// The path of the log file.
+    log_path: PathBuf,
+    /// The writer appending to the log file.
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl Store {
+    /// Opens the store in the given data directory and returns it together with the records
+    /// that were persisted so far.
+    pub fn open(data_directory: &Path) -> std::io::Result<(Store, Vec<Record>)> {
+        std::fs::create_dir_all(data_directory)?;
+
+        let log_path = data_directory.join(LOG_FILE_NAME);
+        let records = read_records(&log_path)?;
+
+        let writer = Mutex::new(BufWriter::new(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&log_path)?,
+        ));
+
+        Ok((Store { log_path, writer }, records))
+    }
+
+    /// Appends the given records to the log and syncs them to disk.
+    pub fn append(&self, records: &[Record]) -> std::io::Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+
+        for record in records {
+            writer.write_all(&record.encode_length_delimited_to_vec())?;
+        }
+
+        writer.flush()?;
+        writer.get_ref().sync_data()
+    }
+
+    /// Replaces the log with the given records, which describe the current state.
+    pub fn compact(&self, records: &[Record]) -> std::io::Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        let temporary_log_path = self.log_path.with_extension("wal.tmp");
+
+        // Write the compacted log next to the current one.
+        {
+            let mut temporary_writer = BufWriter::new(File::create(&temporary_log_path)?);
+
+            for record in records {
+                temporary_writer.write_all(&record.encode_length_delimited_to_vec())?;
+            }
+
+            temporary_writer.flush()?;
+            temporary_writer.get_ref().sync_all()?;
+        }
+
+        // Replace the current log with the compacted one.
+        rename(&temporary_log_path, &self.log_path)?;
+
+        *writer = BufWriter::new(OpenOptions::new().append(true).open(&self.log_path)?);
+
+        Ok(())
+    }
+}
+
+/// Reads all records from the log at the given path.
+fn read_records(log_path: &Path) -> std::io::Result<Vec<Record>> {
+    let mut data = vec![];
+
+    match File::open(log_path) {
+        Ok(file) => {
+            BufReader::new(file).read_to_end(&mut data)?;
+        }
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(error) => return Err(error),
+    }
+
+    let mut records = vec![];
+    let mut remaining_data = data.as_slice();
+
+    while !remaining_data.is_empty() {
+        match Record::decode_length_delimited(&mut remaining_data) {
+            Ok(record) => records.push(record),
+            // The last record was only partially written, e.g. due to a crash.
+            Err(error) => {
+                warn!(
+                    "Ignoring the truncated end of \"{}\": {error}",
+                    log_path.display()
+                );
+                break;
+            }
+        }
+    }
+
+    Ok(records)
+}
+
+/// Wraps the given record kind in a record.
+pub fn record(record_kind: RecordKind) -> Record {
+    Record {
+        record: Some(record_kind),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a record that reserves the given next job ID.
+    fn ids_reserved(next_job_id: u64) -> Record {
+        record(RecordKind::IdsReserved(IdsReserved {
+            next_client_id: 0,
+            next_job_id,
+        }))
+    }
+
+    #[test]
+    fn appended_records_are_read_when_the_store_is_opened_again() {
+        let data_directory = tempfile::tempdir().unwrap();
+
+        {
+            let (store, records) = Store::open(data_directory.path()).unwrap();
+            assert!(records.is_empty());
+
+            store.append(&[ids_reserved(1), ids_reserved(2)]).unwrap();
+            store.append(&[ids_reserved(3)]).unwrap();
+        }
+
+        let (_, records) = Store::open(data_directory.path()).unwrap();
+
+        assert_eq!(records, [ids_reserved(1), ids_reserved(2), ids_reserved(3)]);
+    }
+
+    #[test]
+    fn truncated_last_record_is_ignored() {
+        let data_directory = tempfile::tempdir().unwrap();
+
+        {
+            let (store, _) = Store::open(data_directory.path()).unwrap();
+            store.append(&[ids_reserved(1), ids_reserved(2)]).unwrap();
+        }
+
+        // Cut off the last byte, like a crash while writing.
+        let log_path = data_directory.path().join(LOG_FILE_NAME);
+        let data = std::fs::read(&log_path).unwrap();
+        std::fs::write(&log_path, &data[..data.len() - 1]).unwrap();
+
+        let (_, records) = Store::open(data_directory.path()).unwrap();
+
+        assert_eq!(records, [ids_reserved(1)]);
+    }
+
+    #[test]
+    fn compaction_replaces_the_log_and_further_records_are_appended() {
+        let data_directory = tempfile::tempdir().unwrap();
+
+        {
+            let (store, _) = Store::open(data_directory.path()).unwrap();
+            store.append(&[ids_reserved(1), ids_reserved(2)]).unwrap();
+            store.compact(&[ids_reserved(3)]).unwrap();
+            store.append(&[ids_reserved(4)]).unwrap();
+        }
+
+        let (_, records) = Store::open(data_directory.path()).unwrap();
+
+        assert_eq!(records, [ids_reserved(3), ids_reserved(4)]);
+        assert!(!data_directory.path().join("grid_server.wal.tmp").exists());
+    }
+}
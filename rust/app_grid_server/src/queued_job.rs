@@ -0,0 +1,26 @@
+use crate::fair_share::Share;
+use grid_server_interface::{ClientId, Job, Priority};
+use std::time::Instant;
+
+/// A job that waits in a queue to be dispatched to a grid worker.
+#[derive(Debug)]
+pub struct QueuedJob {
+    pub client_id: ClientId,
+    pub job: Job,
+    pub priority: Priority,
+    pub queued_at: Instant,
+    pub share: Share,
+}
+
+impl QueuedJob {
+    /// Returns the given job of the given client with the given priority and share, queued now.
+    pub fn new(client_id: ClientId, job: Job, priority: Priority, share: Share) -> Self {
+        QueuedJob {
+            client_id,
+            job,
+            priority,
+            queued_at: Instant::now(),
+            share,
+        }
+    }
+}
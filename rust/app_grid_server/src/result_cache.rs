@@ -0,0 +1,219 @@
+use crate::service_versions::ServiceVersions;
+use grid_server_interface::{ResultCacheStatus, ServiceId};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// What a cached result is looked up by: the service and its versions that process a job, and
+/// the hash of the job data.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ResultCacheKey {
+    service_id: ServiceId,
+    service_versions: ServiceVersions,
+    job_data_hash: [u8; 32],
+}
+
+impl ResultCacheKey {
+    /// Returns the key of a job with the given service, service versions and job data.
+    pub fn new(service_id: ServiceId, service_versions: ServiceVersions, job_data: &[u8]) -> Self {
+        ResultCacheKey {
+            service_id,
+            service_versions,
+            job_data_hash: Sha256::digest(job_data).into(),
+        }
+    }
+}
+
+/// The result data of the succeeded jobs, by which identical jobs submitted later are completed
+/// without dispatching them.
+///
+/// The oldest results are dropped when the total size of the result data would exceed the
+/// maximum, and every result is dropped when it expires. The cache is not persisted.
+#[derive(Debug)]
+pub struct ResultCache {
+    /// The keys of the cached results with the time at which they were cached, oldest first.
+    cached_at_and_keys: VecDeque<(Instant, ResultCacheKey)>,
+    /// The number of lookups that found a result.
+    hit_count: u64,
+    /// The largest total size of the cached result data in bytes.
+    max_result_data_size: u64,
+    /// The number of lookups that found no result.
+    miss_count: u64,
+    /// The cached result data, per key.
+    result_data_per_key: HashMap<ResultCacheKey, Vec<u8>>,
+    /// The total size of the cached result data in bytes.
+    result_data_size: u64,
+    /// The time after which a cached result expires.
+    time_to_live: Duration,
+}
+
+impl ResultCache {
+    /// Returns an empty cache with the given bounds.
+    pub fn new(max_result_data_size: u64, time_to_live: Duration) -> Self {
+        ResultCache {
+            cached_at_and_keys: VecDeque::new(),
+            hit_count: 0,
+            max_result_data_size,
+            miss_count: 0,
+            result_data_per_key: HashMap::new(),
+            result_data_size: 0,
+            time_to_live,
+        }
+    }
+
+    /// Returns the cached result data of the given key, if it did not expire, and counts the hit
+    /// or miss.
+    pub fn get(&mut self, key: &ResultCacheKey) -> Option<Vec<u8>> {
+        self.remove_expired();
+
+        let maybe_result_data = self.result_data_per_key.get(key).cloned();
+
+        match &maybe_result_data {
+            Some(_) => self.hit_count += 1,
+            None => self.miss_count += 1,
+        }
+
+        maybe_result_data
+    }
+
+    /// Caches the given result data, unless a result is cached for the given key already or the
+    /// result data is larger than the whole cache. Drops the oldest results to make room.
+    pub fn insert(&mut self, key: ResultCacheKey, result_data: Vec<u8>) {
+        let size = result_data.len() as u64;
+
+        if size > self.max_result_data_size || self.result_data_per_key.contains_key(&key) {
+            return;
+        }
+
+        self.result_data_size += size;
+        self.cached_at_and_keys
+            .push_back((Instant::now(), key.clone()));
+        self.result_data_per_key.insert(key, result_data);
+
+        while self.result_data_size > self.max_result_data_size {
+            self.remove_oldest();
+        }
+    }
+
+    /// Drops every cached result and returns how many there were. The hits and misses are still
+    /// counted.
+    pub fn flush(&mut self) -> u64 {
+        let entry_count = self.result_data_per_key.len() as u64;
+
+        self.cached_at_and_keys.clear();
+        self.result_data_per_key.clear();
+        self.result_data_size = 0;
+
+        entry_count
+    }
+
+    /// Returns the size of the cache and the counts of hits and misses.
+    pub fn status(&mut self) -> ResultCacheStatus {
+        self.remove_expired();
+
+        ResultCacheStatus {
+            entry_count: self.result_data_per_key.len() as u64,
+            result_data_size: self.result_data_size,
+            hit_count: self.hit_count,
+            miss_count: self.miss_count,
+        }
+    }
+
+    /// Drops the results that expired.
+    fn remove_expired(&mut self) {
+        while let Some((cached_at, _)) = self.cached_at_and_keys.front() {
+            if cached_at.elapsed() < self.time_to_live {
+                break;
+            }
+
+            self.remove_oldest();
+        }
+    }
+
+    /// Drops the result that was cached first.
+    fn remove_oldest(&mut self) {
+        if let Some((_, key)) = self.cached_at_and_keys.pop_front() {
+            if let Some(result_data) = self.result_data_per_key.remove(&key) {
+                self.result_data_size -= result_data.len() as u64;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the key of a job of service 1 with the given job data, for version 41 only.
+    fn key(job_data: &[u8]) -> ResultCacheKey {
+        ResultCacheKey::new(1, ServiceVersions::exactly(41), job_data)
+    }
+
+    #[test]
+    fn keys_differ_by_service_versions_and_job_data() {
+        assert_eq!(key(b"job"), key(b"job"));
+        assert_ne!(key(b"job"), key(b"other job"));
+        assert_ne!(
+            key(b"job"),
+            ResultCacheKey::new(2, ServiceVersions::exactly(41), b"job")
+        );
+        assert_ne!(
+            key(b"job"),
+            ResultCacheKey::new(1, ServiceVersions::at_least(41), b"job")
+        );
+    }
+
+    #[test]
+    fn cached_results_are_found_and_counted() {
+        let mut result_cache = ResultCache::new(100, Duration::from_secs(60));
+
+        assert_eq!(result_cache.get(&key(b"job")), None);
+
+        result_cache.insert(key(b"job"), b"result".to_vec());
+        // A result that is cached already is kept.
+        result_cache.insert(key(b"job"), b"other result".to_vec());
+
+        assert_eq!(result_cache.get(&key(b"job")), Some(b"result".to_vec()));
+        assert_eq!(
+            result_cache.status(),
+            ResultCacheStatus {
+                entry_count: 1,
+                result_data_size: 6,
+                hit_count: 1,
+                miss_count: 1,
+            }
+        );
+
+        assert_eq!(result_cache.flush(), 1);
+        assert_eq!(result_cache.get(&key(b"job")), None);
+        assert_eq!(result_cache.status().hit_count, 1);
+    }
+
+    #[test]
+    fn oldest_results_are_evicted_to_stay_within_the_size() {
+        let mut result_cache = ResultCache::new(10, Duration::from_secs(60));
+
+        result_cache.insert(key(b"first"), vec![1; 4]);
+        result_cache.insert(key(b"second"), vec![2; 4]);
+        result_cache.insert(key(b"third"), vec![3; 4]);
+        // A result larger than the whole cache is not cached.
+        result_cache.insert(key(b"large"), vec![4; 11]);
+
+        assert_eq!(result_cache.get(&key(b"first")), None);
+        assert_eq!(result_cache.get(&key(b"second")), Some(vec![2; 4]));
+        assert_eq!(result_cache.get(&key(b"third")), Some(vec![3; 4]));
+        assert_eq!(result_cache.get(&key(b"large")), None);
+        assert_eq!(result_cache.status().result_data_size, 8);
+    }
+
+    #[test]
+    fn results_expire_after_their_time_to_live() {
+        let mut result_cache = ResultCache::new(100, Duration::ZERO);
+
+        result_cache.insert(key(b"job"), b"result".to_vec());
+
+        assert_eq!(result_cache.get(&key(b"job")), None);
+        assert_eq!(result_cache.status().entry_count, 0);
+        assert_eq!(result_cache.status().result_data_size, 0);
+    }
+}
@@ -0,0 +1,68 @@
+use grid_server_interface::ServiceVersion;
+use std::fmt::{Display, Formatter};
+
+/// The versions of a service that may process a queued job.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ServiceVersions {
+    /// The oldest version.
+    pub oldest: ServiceVersion,
+    /// Whether every newer version may process the job, too.
+    pub newer: bool,
+}
+
+impl ServiceVersions {
+    /// Returns only the given version.
+    pub fn exactly(service_version: ServiceVersion) -> Self {
+        ServiceVersions {
+            oldest: service_version,
+            newer: false,
+        }
+    }
+
+    /// Returns the given version and every newer one.
+    pub fn at_least(service_version: ServiceVersion) -> Self {
+        ServiceVersions {
+            oldest: service_version,
+            newer: true,
+        }
+    }
+
+    /// Returns whether the given version is one of these versions.
+    pub fn contains(&self, service_version: ServiceVersion) -> bool {
+        service_version == self.oldest || (self.newer && service_version > self.oldest)
+    }
+}
+
+impl Display for ServiceVersions {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.newer {
+            true => write!(formatter, ">={}", self.oldest),
+            false => write!(formatter, "{}", self.oldest),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_versions_contain_only_their_version() {
+        let service_versions = ServiceVersions::exactly(41);
+
+        assert!(!service_versions.contains(40));
+        assert!(service_versions.contains(41));
+        assert!(!service_versions.contains(42));
+        assert_eq!(service_versions.to_string(), "41");
+    }
+
+    #[test]
+    fn version_ranges_contain_every_newer_version() {
+        let service_versions = ServiceVersions::at_least(41);
+
+        assert!(!service_versions.contains(40));
+        assert!(service_versions.contains(41));
+        assert!(service_versions.contains(u32::MAX));
+        assert_eq!(service_versions.to_string(), ">=41");
+    }
+}
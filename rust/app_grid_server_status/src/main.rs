@@ -1,34 +1,286 @@
 use grid_client::connect_async_grid_client;
+use grid_server_interface::{
+    take_role_secret_from_command_line_arguments, ClientRole, DeadLetter,
+    ResponseToControllerStatusGet, TlsPaths, ROLE_SECRET_USAGE, TLS_USAGE,
+};
+use serde_json::{json, Value};
 use std::env::args;
 use std::process::exit;
 
-fn print_usage_and_stop() {
-    println!("Usage: `grid-server-status <SERVER_ADDRESS>`");
+fn print_usage_and_stop() -> ! {
+    println!(
+        "Usage: `grid-server-status <SERVER_ADDRESS> [dead-letters|drain|flush-result-cache] [--json] {TLS_USAGE} {ROLE_SECRET_USAGE}`"
+    );
     exit(-1);
 }
 
+/// Prints the given rows as a table with a header, aligning the columns.
+fn print_table(header: &[&str], rows: &[Vec<String>]) {
+    // Get the width of every column.
+    let mut column_widths: Vec<usize> = header.iter().map(|title| title.len()).collect();
+
+    for row in rows {
+        for (column_width, cell) in column_widths.iter_mut().zip(row) {
+            *column_width = (*column_width).max(cell.len());
+        }
+    }
+
+    let print_row = |cells: Vec<&str>| {
+        let line = cells
+            .iter()
+            .zip(&column_widths)
+            .map(|(cell, column_width)| format!("{cell:<column_width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+
+        println!("{}", line.trim_end());
+    };
+
+    print_row(header.to_vec());
+
+    for row in rows {
+        print_row(row.iter().map(String::as_str).collect());
+    }
+}
+
+/// Converts the given status into JSON.
+fn json_from_status(status: &ResponseToControllerStatusGet) -> Value {
+    json!({
+        "clients": status.clients.iter().map(|client| json!({
+            "client_id": client.client_id,
+            "client_description": client.client_description,
+            "host_id": client.host_id,
+            "user_id": client.user_id,
+            "last_access": client.last_access,
+            "pending_result_count": client.pending_result_count,
+        })).collect::<Vec<_>>(),
+        "queues": status.queues.iter().map(|queue| json!({
+            "service_id": queue.service_id,
+            "service_version": queue.service_version,
+            "newer_service_versions": queue.newer_service_versions,
+            "priority": queue.priority,
+            "queued_job_count": queue.queued_job_count,
+        })).collect::<Vec<_>>(),
+        "workers": status.workers.iter().map(|worker| json!({
+            "worker_client_id": worker.worker_client_id,
+            "dispatched_job_ids": worker.dispatched_job_ids,
+        })).collect::<Vec<_>>(),
+        "result_cache": status.result_cache.as_ref().map(|result_cache| json!({
+            "entry_count": result_cache.entry_count,
+            "result_data_size": result_cache.result_data_size,
+            "hit_count": result_cache.hit_count,
+            "miss_count": result_cache.miss_count,
+        })),
+    })
+}
+
+/// Prints the given status as tables.
+fn print_status(status: &ResponseToControllerStatusGet) {
+    println!("Clients:");
+    print_table(
+        &[
+            "ID",
+            "DESCRIPTION",
+            "HOST",
+            "USER",
+            "LAST ACCESS",
+            "PENDING RESULTS",
+        ],
+        &status
+            .clients
+            .iter()
+            .map(|client| {
+                vec![
+                    client.client_id.to_string(),
+                    client.client_description.clone(),
+                    client.host_id.clone(),
+                    client.user_id.clone(),
+                    client.last_access.clone(),
+                    client.pending_result_count.to_string(),
+                ]
+            })
+            .collect::<Vec<_>>(),
+    );
+
+    println!();
+    println!("Queues:");
+    print_table(
+        &["SERVICE ID", "SERVICE VERSION", "PRIORITY", "QUEUED JOBS"],
+        &status
+            .queues
+            .iter()
+            .map(|queue| {
+                vec![
+                    queue.service_id.to_string(),
+                    // Newer versions may process the jobs, too.
+                    match queue.newer_service_versions {
+                        true => format!(">={}", queue.service_version),
+                        false => queue.service_version.to_string(),
+                    },
+                    queue.priority.to_string(),
+                    queue.queued_job_count.to_string(),
+                ]
+            })
+            .collect::<Vec<_>>(),
+    );
+
+    println!();
+    println!("Workers:");
+    print_table(
+        &["WORKER ID", "JOBS IN FLIGHT", "JOB IDS"],
+        &status
+            .workers
+            .iter()
+            .map(|worker| {
+                vec![
+                    worker.worker_client_id.to_string(),
+                    worker.dispatched_job_ids.len().to_string(),
+                    worker
+                        .dispatched_job_ids
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(","),
+                ]
+            })
+            .collect::<Vec<_>>(),
+    );
+
+    if let Some(result_cache) = &status.result_cache {
+        println!();
+        println!("Result cache:");
+        print_table(
+            &["ENTRIES", "BYTES", "HITS", "MISSES"],
+            &[vec![
+                result_cache.entry_count.to_string(),
+                result_cache.result_data_size.to_string(),
+                result_cache.hit_count.to_string(),
+                result_cache.miss_count.to_string(),
+            ]],
+        );
+    }
+}
+
+/// Converts the given dead letters into JSON.
+fn json_from_dead_letters(dead_letters: &[DeadLetter]) -> Value {
+    json!(dead_letters
+        .iter()
+        .map(|dead_letter| json!({
+            "job_id": dead_letter.job.as_ref().map(|job| job.job_id).unwrap_or_default(),
+            "client_id": dead_letter.client_id,
+            "service_id": dead_letter.service_id,
+            "service_version": dead_letter.service_version,
+            "attempts": dead_letter.attempts,
+            "error_message": dead_letter.error_message,
+            "failed_at": dead_letter.failed_at,
+        }))
+        .collect::<Vec<_>>())
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
 
     // Get the given command line arguments.
-    let command_line_arguments: Vec<_> = args().collect();
+    let mut command_line_arguments: Vec<_> = args().collect();
+
+    // Whether to print JSON instead of tables.
+    let print_json = command_line_arguments
+        .iter()
+        .any(|command_line_argument| command_line_argument == "--json");
+    command_line_arguments.retain(|command_line_argument| command_line_argument != "--json");
+
+    // The paths for connecting over TLS.
+    let tls_paths = TlsPaths::take_from_command_line_arguments(&mut command_line_arguments)
+        .unwrap_or_else(|error_message| {
+            eprintln!("{error_message}");
+            print_usage_and_stop()
+        });
+
+    // The secret of the controller role, if the grid server requires one.
+    let role_secret = take_role_secret_from_command_line_arguments(&mut command_line_arguments)
+        .unwrap_or_else(|error_message| {
+            eprintln!("{error_message}");
+            print_usage_and_stop()
+        });
 
     // Too few command line arguments are given.
-    if command_line_arguments.is_empty() {
+    if command_line_arguments.len() < 2 {
         print_usage_and_stop();
     }
 
     let server_address = &command_line_arguments[1];
 
     // Try to connect to the server.
-    let mut grid_client = connect_async_grid_client(server_address, "monitor".to_string()).await?;
+    let mut grid_client = connect_async_grid_client(
+        server_address,
+        "monitor".to_string(),
+        ClientRole::Controller,
+        role_secret,
+        None,
+        &tls_paths,
+    )
+    .await?;
+
+    match command_line_arguments.get(2).map(String::as_str) {
+        None => {
+            // Try to get the server status.
+            let status_response = grid_client.controller_get_status().await?;
+            let status_response = status_response.get_ref();
+
+            if print_json {
+                println!("{}", json_from_status(status_response));
+            } else {
+                print_status(status_response);
+            }
+        }
+        Some("dead-letters") => {
+            // Try to get the jobs that failed too often.
+            let dead_letters_response = grid_client.controller_get_dead_letters().await?;
+            let dead_letters_response = dead_letters_response.get_ref();
 
-    // Try to get the server status.
-    let status_response = grid_client.controller_get_status().await?;
-    let status_response = status_response.get_ref();
+            if print_json {
+                println!(
+                    "{}",
+                    json_from_dead_letters(&dead_letters_response.dead_letters)
+                );
+                return Ok(());
+            }
 
-    println!("{}", status_response.status);
+            for dead_letter in &dead_letters_response.dead_letters {
+                println!(
+                    "job {} of client {} for service {} {}: failed {} times, last at {}: {}",
+                    dead_letter
+                        .job
+                        .as_ref()
+                        .map(|job| job.job_id)
+                        .unwrap_or_default(),
+                    dead_letter.client_id,
+                    dead_letter.service_id,
+                    dead_letter.service_version,
+                    dead_letter.attempts,
+                    dead_letter.failed_at,
+                    dead_letter.error_message
+                );
+            }
+        }
+        Some("drain") => {
+            // Try to request the server to stop gracefully.
+            grid_client.controller_drain_server().await?;
+
+            println!("The server drains and stops");
+        }
+        Some("flush-result-cache") => {
+            // Try to drop the cached results.
+            let flush_response = grid_client.controller_flush_result_cache().await?;
+
+            println!(
+                "Flushed {} cached results",
+                flush_response.get_ref().flushed_entry_count
+            );
+        }
+        Some(_) => print_usage_and_stop(),
+    }
 
     Ok(())
 }
@@ -1,14 +1,23 @@
 use grid_client::connect_async_grid_client;
-use grid_server_interface::ServiceId;
-use log::{error, info};
+use grid_server_interface::{
+    compression_encoding_from_str, take_role_secret_from_command_line_arguments, ClientRole, Job,
+    JobId, ResponseToWorkerExchange, ServiceId, TlsPaths, ROLE_SECRET_USAGE, TLS_USAGE,
+};
+use log::{error, info, warn};
+use std::collections::{HashSet, VecDeque};
 use std::env::args;
 use std::process::exit;
 use std::ptr::addr_of_mut;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use std::thread;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+/// How long the server may wait for a job to be queued before answering without one.
+const WAIT_DURATION: Duration = Duration::from_secs(10);
+
+/// The number of jobs the worker holds at once, unless given on the command line.
+const DEFAULT_JOB_COUNT: u32 = 2;
+
 /// The C-ABI signature of the service function.
 type ServiceFunction = unsafe extern "C" fn(
     data_in: *const libc::c_void,
@@ -17,7 +26,8 @@ type ServiceFunction = unsafe extern "C" fn(
     size_out: *mut libc::c_longlong,
 );
 
-///
+/// Returns the description with which the grid worker for the given service library registers
+/// at the grid server.
 fn worker_client_description(
     service_id: ServiceId,
     service_version: ServiceId,
@@ -31,13 +41,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
 
     // Get the given command line arguments.
-    let command_line_arguments: Vec<_> = args().collect();
+    let mut command_line_arguments: Vec<_> = args().collect();
+
+    // The paths for connecting over TLS.
+    let tls_paths = TlsPaths::take_from_command_line_arguments(&mut command_line_arguments)?;
+
+    // The secret of the worker role, if the grid server requires one.
+    let role_secret = take_role_secret_from_command_line_arguments(&mut command_line_arguments)?;
 
     // Too few command line arguments are given.
     if command_line_arguments.len() < 5 {
         error!(
             "Usage: grid-worker <SERVER_ADDRESS> <SERVICE_ID> <SERVICE_VERSION> \
-             <PATH_SERVICE_FUNCTION>"
+             <PATH_SERVICE_FUNCTION> [<JOB_COUNT> [gzip|zstd|none]] {TLS_USAGE} \
+             {ROLE_SECRET_USAGE}"
         );
         exit(-1);
     }
@@ -46,6 +63,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let service_id = command_line_arguments[2].parse()?;
     let service_version = command_line_arguments[3].parse()?;
     let path_service_library = &command_line_arguments[4];
+    let job_count: u32 = match command_line_arguments.get(5) {
+        Some(job_count) => job_count.parse()?,
+        None => DEFAULT_JOB_COUNT,
+    }
+    .max(1);
+    let compression = match command_line_arguments.get(6) {
+        Some(compression) => compression_encoding_from_str(compression)?,
+        None => None,
+    };
 
     // Try to load the service library.
     let service_library = unsafe { libloading::Library::new(path_service_library)? };
@@ -55,9 +81,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         unsafe { service_library.get(b"service_function")? };
 
     // Try to connect to the server.
-    let mut grid_client = connect_async_grid_client(
+    let grid_client = connect_async_grid_client(
         server_address,
         worker_client_description(service_id, service_version, path_service_library),
+        ClientRole::Worker,
+        role_secret,
+        compression,
+        &tls_paths,
     )
     .await?;
 
@@ -71,6 +101,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let mut result: Option<grid_server_interface::Result> = None;
 
+    // The jobs fetched from the server that were not processed yet.
+    let mut jobs: VecDeque<Job> = VecDeque::new();
+
+    // The IDs of the jobs whose leases the worker holds, from their dispatch until they are
+    // processed. Their leases are extended together.
+    let held_job_ids: Arc<Mutex<HashSet<JobId>>> = Arc::new(Mutex::new(HashSet::new()));
+
+    // Extends the leases of the held jobs, once the lease duration is known.
+    let mut lease_extender = None;
+
     loop {
         // The grid worker was requested to be stopped.
         if stop_worker.load(Ordering::Relaxed) {
@@ -78,47 +118,142 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             exit(0);
         }
 
-        // Try to fetch a job from the server and maybe also send a result to the server.
-        let worker_server_exchange_response = grid_client
-            .worker_server_exchange(service_id, service_version, result.clone())
-            .await?;
+        // Send the result to the server and fetch more jobs, while the next buffered job is
+        // processed. Only wait for new jobs if there is nothing else to do.
+        let exchange = {
+            let mut grid_client = grid_client.clone();
+            let result = result.take();
+            let wait_duration = if jobs.is_empty() {
+                WAIT_DURATION
+            } else {
+                Duration::ZERO
+            };
+            let missing_job_count = job_count - jobs.len() as u32;
+            let held_job_ids = Arc::clone(&held_job_ids);
+
+            tokio::spawn(async move {
+                if missing_job_count == 0 {
+                    // Only send the result.
+                    if let Some(result) = result {
+                        grid_client.worker_submit_result(result).await?;
+                    }
+
+                    return Ok(ResponseToWorkerExchange::default());
+                }
+
+                grid_client
+                    .worker_server_exchange(
+                        service_id,
+                        service_version,
+                        result,
+                        wait_duration,
+                        missing_job_count,
+                    )
+                    .await
+                    .map(|response| {
+                        let response = response.into_inner();
+
+                        // Hold the leases of the new jobs right away, so that they are extended
+                        // while the current job is processed.
+                        held_job_ids.lock().unwrap().extend(
+                            response
+                                .job
+                                .iter()
+                                .chain(&response.prefetched_jobs)
+                                .map(|job| job.job_id),
+                        );
 
-        let worker_server_exchange_response = worker_server_exchange_response.get_ref();
+                        response
+                    })
+            })
+        };
 
-        let job =
-            // There is a new job from the server.
-            if let Some(job) = &worker_server_exchange_response.job {
-                job
+        // There is a job in the buffer, whose lease is still held.
+        if let Some(job) = jobs.pop_front() {
+            if !held_job_ids.lock().unwrap().contains(&job.job_id) {
+                warn!(
+                    "Skipping job with ID {}, because its lease was lost",
+                    job.job_id
+                );
+            } else {
+                unsafe {
+                    let data_in: *const libc::c_void = job.job_data.as_ptr() as *const libc::c_void;
+                    let size_in: libc::c_longlong = job.job_data.len().try_into().unwrap();
+                    let mut data_out: libc::c_longlong = 0;
+                    let mut size_out: libc::c_longlong = 0;
+
+                    // Process the job with the service function.
+                    service_function(
+                        data_in,
+                        size_in,
+                        addr_of_mut!(data_out) as *mut libc::c_void,
+                        addr_of_mut!(size_out),
+                    );
+
+                    // TODO: Call the libraries free function.
+
+                    // TODO
+                    result = None;
+                }
+
+                // The job's result is sent with the next exchange, well within the extended lease.
+                held_job_ids.lock().unwrap().remove(&job.job_id);
             }
-            // There is no new job from the server.
-            else {
-                result = None;
-                thread::sleep(Duration::from_millis(1000));
-                continue;
-            };
+        }
+
+        let worker_server_exchange_response = exchange.await??;
 
-        unsafe {
-            let data_in: *const libc::c_void = job.job_data.as_ptr() as *const libc::c_void;
-            let size_in: libc::c_longlong = job.job_data.len().try_into().unwrap();
-            let mut data_out: libc::c_longlong = 0;
-            let mut size_out: libc::c_longlong = 0;
+        // Extend the leases of the held jobs regularly, once the lease duration is known.
+        if lease_extender.is_none() && worker_server_exchange_response.lease_milliseconds > 0 {
+            let lease_duration =
+                Duration::from_millis(worker_server_exchange_response.lease_milliseconds);
+            let mut grid_client = grid_client.clone();
+            let held_job_ids = Arc::clone(&held_job_ids);
 
-            // Process the job with the service function.
-            service_function(
-                data_in,
-                size_in,
-                addr_of_mut!(data_out) as *mut libc::c_void,
-                addr_of_mut!(size_out),
-            );
+            lease_extender = Some(tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(lease_duration / 2).await;
 
-            // TODO: Call the libraries free function.
+                    let job_ids: Vec<JobId> =
+                        held_job_ids.lock().unwrap().iter().copied().collect();
 
-            // TODO
-            result = None;
+                    if job_ids.is_empty() {
+                        continue;
+                    }
+
+                    match grid_client.worker_extend_lease(job_ids).await {
+                        Ok(response) => {
+                            let mut held_job_ids = held_job_ids.lock().unwrap();
+
+                            // Forget the jobs whose lease was lost, they are not processed.
+                            for job_id in &response.get_ref().lost_job_ids {
+                                warn!("Lost the lease of job with ID {job_id}");
+                                held_job_ids.remove(job_id);
+                            }
+                        }
+                        Err(error) => {
+                            warn!("Could not extend the job leases: {error}")
+                        }
+                    }
+                }
+            }));
         }
-    }
 
-    info!("Done.");
+        // Jobs processed before were cancelled in the meantime.
+        for job_id in &worker_server_exchange_response.cancelled_job_ids {
+            info!("Job with ID {job_id} was cancelled");
+            held_job_ids.lock().unwrap().remove(job_id);
+        }
+
+        // Drop the buffered jobs that were cancelled.
+        jobs.retain(|job| {
+            !worker_server_exchange_response
+                .cancelled_job_ids
+                .contains(&job.job_id)
+        });
 
-    Ok(())
+        // Buffer the new jobs from the server.
+        jobs.extend(worker_server_exchange_response.job);
+        jobs.extend(worker_server_exchange_response.prefetched_jobs);
+    }
 }
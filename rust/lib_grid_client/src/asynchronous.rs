@@ -1,29 +1,106 @@
 use grid_server_interface::grid_server_interface::{
-    RequestFromControllerStatusGet, ResponseToControllerStatusGet,
+    RequestFromControllerDeadLettersGet, RequestFromControllerResultCacheFlush,
+    RequestFromControllerServerDrain, RequestFromControllerStatusGet,
+    ResponseToControllerDeadLettersGet, ResponseToControllerResultCacheFlush,
+    ResponseToControllerServerDrain, ResponseToControllerStatusGet,
 };
 use grid_server_interface::{
-    ClientId, GridServerClient, JobQuery, RequestFromClientJobSubmit, RequestFromClientRegister,
-    RequestFromClientResultFetch, RequestFromWorkerExchange, RequestFromWorkerResultSubmit,
-    ResponseToClientJobSubmit, ResponseToClientResultFetch, ResponseToWorkerExchange,
-    ResponseToWorkerResultSubmit, ServiceId, ServiceVersion,
+    split_into_chunks, ClientRole, CompressionEncoding, GridServerClient, Job, JobId, JobQuery,
+    Labels, Priority, RequestFromClientJobCancel, RequestFromClientJobStatusGet,
+    RequestFromClientJobSubmit, RequestFromClientJobTraceGet, RequestFromClientJobsSubmit,
+    RequestFromClientRegister, RequestFromClientResultAcknowledge, RequestFromClientResultFetch,
+    RequestFromClientResultStream, RequestFromWorkerExchange, RequestFromWorkerJobDownload,
+    RequestFromWorkerLeaseExtend, RequestFromWorkerResultSubmit, ResponseToClientJobCancel,
+    ResponseToClientJobStatusGet, ResponseToClientJobSubmit, ResponseToClientJobTraceGet,
+    ResponseToClientJobsSubmit, ResponseToClientResultAcknowledge, ResponseToClientResultFetch,
+    ResponseToWorkerExchange, ResponseToWorkerLeaseExtend, ResponseToWorkerResultSubmit, ServiceId,
+    ServiceVersion, ServiceVersionRequirement, TlsPaths, DATA_CHUNK_SIZE,
 };
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio_stream::{Stream, StreamExt};
 use tonic::transport::Channel;
-use tonic::{Request, Response, Status};
+use tonic::{Request, Response, Status, Streaming};
 #[cfg(not(target_os = "windows"))]
 use users::{get_current_uid, get_user_by_uid};
 
-///
+/// The number of jobs sent per message by `client_submit_jobs()`.
+const JOB_BATCH_SIZE: usize = 1000;
+
+/// A client of the grid server, which is registered with the session token.
+#[derive(Clone)]
 pub struct AsyncGridClient {
-    client_id: ClientId,
     grid_client: GridServerClient<Channel>,
+    session_token: String,
 }
 
-///
+/// The results streamed to a client by `AsyncGridClient::client_stream_results()`, which arrive
+/// in chunks of their result data. Yields every result once all of its chunks arrived.
+pub struct ResultStream {
+    chunk_stream: Streaming<grid_server_interface::Result>,
+    // The result whose result data is not complete yet.
+    incomplete_result: Option<grid_server_interface::Result>,
+}
+
+impl ResultStream {
+    /// Returns the next result once all chunks of its result data arrived, or `None` if the
+    /// stream ended.
+    pub async fn message(&mut self) -> Result<Option<grid_server_interface::Result>, Status> {
+        self.next().await.transpose()
+    }
+}
+
+/// Returns the error for the given result, whose result data is missing chunks.
+fn incomplete_result_data(result: &grid_server_interface::Result) -> Status {
+    Status::data_loss(format!(
+        "The result data of job with ID {} is incomplete",
+        result.job_id
+    ))
+}
+
+impl Stream for ResultStream {
+    type Item = Result<grid_server_interface::Result, Status>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let chunk = match ready!(Pin::new(&mut self.chunk_stream).poll_next(cx)) {
+                Some(Ok(chunk)) => chunk,
+                Some(Err(status)) => return Poll::Ready(Some(Err(status))),
+                None => {
+                    return Poll::Ready(
+                        self.incomplete_result
+                            .take()
+                            .map(|result| Err(incomplete_result_data(&result))),
+                    )
+                }
+            };
+
+            // Concatenate the result data of the consecutive chunks with the same job ID.
+            let result = match self.incomplete_result.take() {
+                Some(mut result) if chunk.job_id == result.job_id => {
+                    result.result_data.extend(chunk.result_data);
+                    result
+                }
+                Some(result) => return Poll::Ready(Some(Err(incomplete_result_data(&result)))),
+                None => chunk,
+            };
+
+            if (result.result_data.len() as u64) < result.result_data_size {
+                self.incomplete_result = Some(result);
+            } else {
+                return Poll::Ready(Some(Ok(result)));
+            }
+        }
+    }
+}
+
+/// Returns the name of this host, if it can be determined.
 fn client_hostname() -> Option<String> {
     hostname::get().ok()?.into_string().ok()
 }
 
-///
+/// Returns the name of the user running this process, if it can be determined.
 #[cfg(target_os = "windows")]
 fn user_id() -> Option<String> {
     // TODO
@@ -31,7 +108,7 @@ fn user_id() -> Option<String> {
     Some("".to_string())
 }
 
-///
+/// Returns the name of the user running this process, if it can be determined.
 #[cfg(not(target_os = "windows"))]
 fn user_id() -> Option<String> {
     let user = get_user_by_uid(get_current_uid())?;
@@ -39,12 +116,30 @@ fn user_id() -> Option<String> {
     Some(user.name().to_str()?.to_string())
 }
 
+/// Connects to the grid server at the given address and registers as a client with the given
+/// role, which decides the RPCs that the client may call. The role secret is required if the grid
+/// server is given a secret for the role. The requests are
+/// compressed with the given encoding, if any, which the grid server always accepts. The responses
+/// are compressed if the grid server is configured to.
 ///
+/// The connection uses TLS if the given paths contain a CA certificate, see `TlsPaths`.
 pub async fn connect_async_grid_client(
     server_address: &str,
     client_description: String,
+    role: ClientRole,
+    role_secret: Option<String>,
+    compression: Option<CompressionEncoding>,
+    tls_paths: &TlsPaths,
 ) -> Result<AsyncGridClient, Box<dyn std::error::Error>> {
-    let mut grid_client = GridServerClient::connect(format!("http://{}", server_address)).await?;
+    let channel = tls_paths.endpoint(server_address)?.connect().await?;
+
+    let mut grid_client = GridServerClient::new(channel)
+        .accept_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Zstd);
+
+    if let Some(compression) = compression {
+        grid_client = grid_client.send_compressed(compression);
+    }
 
     // Register the client with the server.
     let register_client_response = grid_client
@@ -52,84 +147,453 @@ pub async fn connect_async_grid_client(
             client_description,
             host_id: client_hostname().unwrap_or_default().to_lowercase(),
             user_id: user_id().unwrap_or_default().to_lowercase(),
+            role: role.into(),
+            role_secret: role_secret.unwrap_or_default(),
         }))
         .await?;
 
     Ok(AsyncGridClient {
-        client_id: register_client_response.get_ref().client_id,
         grid_client,
+        session_token: register_client_response.into_inner().session_token,
     })
 }
 
 impl AsyncGridClient {
-    ///
+    /// Lets the server drop the given results, which this client downloaded via
+    /// `client_download_results()`.
+    pub async fn client_acknowledge_results(
+        &mut self,
+        job_ids: Vec<JobId>,
+    ) -> Result<Response<ResponseToClientResultAcknowledge>, Status> {
+        self.grid_client
+            .client_acknowledge_results(Request::new(RequestFromClientResultAcknowledge {
+                session_token: self.session_token.clone(),
+                job_ids,
+            }))
+            .await
+    }
+
+    /// Withdraws the given jobs, which were submitted by this client, if they have every label of
+    /// the given filter. Withdraws every job of this client with these labels if no jobs are given.
+    pub async fn client_cancel_job(
+        &mut self,
+        job_ids: Vec<JobId>,
+        label_filter: Labels,
+    ) -> Result<Response<ResponseToClientJobCancel>, Status> {
+        self.grid_client
+            .client_cancel_job(Request::new(RequestFromClientJobCancel {
+                session_token: self.session_token.clone(),
+                job_ids,
+                label_filter,
+            }))
+            .await
+    }
+
+    /// Returns a stream of the results that arrived for this client for the jobs with every label
+    /// of the given filter, in chunks of their result data. Consecutive results with the same job
+    /// ID belong together. The server keeps the results until they are acknowledged via
+    /// `client_acknowledge_results()`.
+    pub async fn client_download_results(
+        &mut self,
+        label_filter: Labels,
+    ) -> Result<Response<Streaming<grid_server_interface::Result>>, Status> {
+        self.grid_client
+            .client_download_results(Request::new(RequestFromClientResultFetch {
+                session_token: self.session_token.clone(),
+                label_filter,
+            }))
+            .await
+    }
+
+    /// Returns the results that arrived for this client for the jobs with every label of the given
+    /// filter. They are downloaded in chunks, so that there is no limit on the size of the result
+    /// data, and acknowledged once all of them arrived.
     pub async fn client_fetch_results(
         &mut self,
+        label_filter: Labels,
     ) -> Result<Response<ResponseToClientResultFetch>, Status> {
+        let mut result_stream = self
+            .client_download_results(label_filter)
+            .await?
+            .into_inner();
+        let mut results: Vec<grid_server_interface::Result> = vec![];
+
+        // Concatenate the result data of consecutive chunks with the same job ID.
+        while let Some(chunk) = result_stream.message().await? {
+            match results.last_mut() {
+                Some(result) if result.job_id == chunk.job_id => {
+                    result.result_data.extend(chunk.result_data);
+                }
+                _ => results.push(chunk),
+            }
+        }
+
+        if !results.is_empty() {
+            self.client_acknowledge_results(results.iter().map(|result| result.job_id).collect())
+                .await?;
+        }
+
+        Ok(Response::new(ResponseToClientResultFetch { results }))
+    }
+
+    /// Returns a stream that yields every result as soon as the server receives it, starting with
+    /// the results that arrived while this client was not connected. The result data is streamed
+    /// in chunks, so that there is no limit on its size.
+    pub async fn client_stream_results(&mut self) -> Result<Response<ResultStream>, Status> {
+        Ok(self
+            .grid_client
+            .client_stream_results(Request::new(RequestFromClientResultStream {
+                session_token: self.session_token.clone(),
+            }))
+            .await?
+            .map(|chunk_stream| ResultStream {
+                chunk_stream,
+                incomplete_result: None,
+            }))
+    }
+
+    /// Returns the status of the given jobs, which were submitted by this client, if they have
+    /// every label of the given filter. Returns the status of every job of this client with these
+    /// labels if no jobs are given.
+    pub async fn client_get_job_status(
+        &mut self,
+        job_ids: Vec<JobId>,
+        label_filter: Labels,
+    ) -> Result<Response<ResponseToClientJobStatusGet>, Status> {
         self.grid_client
-            .client_fetch_results(Request::new(RequestFromClientResultFetch {
-                client_id: self.client_id,
+            .client_get_job_status(Request::new(RequestFromClientJobStatusGet {
+                session_token: self.session_token.clone(),
+                job_ids,
+                label_filter,
             }))
             .await
     }
 
-    ///
+    /// Returns the lifecycle of the given jobs, which were submitted by this client.
+    pub async fn client_get_job_trace(
+        &mut self,
+        job_ids: Vec<JobId>,
+    ) -> Result<Response<ResponseToClientJobTraceGet>, Status> {
+        self.grid_client
+            .client_get_job_trace(Request::new(RequestFromClientJobTraceGet {
+                session_token: self.session_token.clone(),
+                job_ids,
+            }))
+            .await
+    }
+
+    /// Requests the server to stop gracefully. It stops accepting and dispatching jobs, waits for
+    /// the results of the dispatched jobs and for the clients to fetch them, and then exits.
+    pub async fn controller_drain_server(
+        &mut self,
+    ) -> Result<Response<ResponseToControllerServerDrain>, Status> {
+        self.grid_client
+            .controller_drain_server(Request::new(RequestFromControllerServerDrain {
+                session_token: self.session_token.clone(),
+            }))
+            .await
+    }
+
+    /// Drops every cached result, so that the jobs submitted later are dispatched again.
+    pub async fn controller_flush_result_cache(
+        &mut self,
+    ) -> Result<Response<ResponseToControllerResultCacheFlush>, Status> {
+        self.grid_client
+            .controller_flush_result_cache(Request::new(RequestFromControllerResultCacheFlush {
+                session_token: self.session_token.clone(),
+            }))
+            .await
+    }
+
+    /// Returns the jobs that failed too often and are not dispatched anymore.
+    pub async fn controller_get_dead_letters(
+        &mut self,
+    ) -> Result<Response<ResponseToControllerDeadLettersGet>, Status> {
+        self.grid_client
+            .controller_get_dead_letters(Request::new(RequestFromControllerDeadLettersGet {
+                session_token: self.session_token.clone(),
+            }))
+            .await
+    }
+
+    /// Returns the clients, queues, dispatched jobs and result cache of the server.
     pub async fn controller_get_status(
         &mut self,
     ) -> Result<Response<ResponseToControllerStatusGet>, Status> {
         self.grid_client
             .controller_get_status(Request::new(RequestFromControllerStatusGet {
-                client_id: self.client_id,
+                session_token: self.session_token.clone(),
             }))
             .await
     }
 
-    ///
+    /// Submits the given job for an exact service version or any version that meets the given
+    /// requirement. Jobs with a higher priority are dispatched first, the priority is 0 if not
+    /// given. The given labels are passed to the worker and returned with the result.
     pub async fn client_submit_job(
         &mut self,
         service_id: ServiceId,
-        service_version: ServiceVersion,
+        service_version: impl Into<ServiceVersionRequirement>,
         job_data: Vec<u8>,
+        priority: Option<Priority>,
+        labels: Labels,
     ) -> Result<Response<ResponseToClientJobSubmit>, Status> {
+        // The job data is too large for a single message.
+        if job_data.len() > DATA_CHUNK_SIZE {
+            return self
+                .client_upload_job(service_id, service_version, job_data, priority, labels)
+                .await;
+        }
+
+        let (service_version, service_version_constraint) = service_version.into().to_submission();
+
         self.grid_client
             .client_submit_job(Request::new(RequestFromClientJobSubmit {
-                client_id: self.client_id,
+                session_token: self.session_token.clone(),
                 job_data,
+                priority,
                 service_id,
                 service_version,
+                service_version_constraint,
+                labels,
             }))
             .await
     }
 
+    /// Submits the given job like `client_submit_job()`, but uploads the job data in chunks, so
+    /// that there is no limit on its size other than the grid server's.
+    pub async fn client_upload_job(
+        &mut self,
+        service_id: ServiceId,
+        service_version: impl Into<ServiceVersionRequirement>,
+        job_data: Vec<u8>,
+        priority: Option<Priority>,
+        labels: Labels,
+    ) -> Result<Response<ResponseToClientJobSubmit>, Status> {
+        let (service_version, service_version_constraint) = service_version.into().to_submission();
+
+        let requests: Vec<RequestFromClientJobSubmit> = split_into_chunks(job_data)
+            .into_iter()
+            .map(|job_data| RequestFromClientJobSubmit {
+                session_token: self.session_token.clone(),
+                job_data,
+                priority,
+                service_id,
+                service_version,
+                service_version_constraint: service_version_constraint.clone(),
+                labels: labels.clone(),
+            })
+            .collect();
+
+        self.grid_client
+            .client_upload_job(Request::new(tokio_stream::iter(requests)))
+            .await
+    }
+
+    /// Submits the given jobs for the same service, priority and labels and returns their job
+    /// IDs, in order.
     ///
+    /// The jobs are streamed to the server in batches and queued together once all of them arrived.
+    /// Jobs whose data is too large for a batch are streamed in chunks of their own messages.
+    pub async fn client_submit_jobs(
+        &mut self,
+        service_id: ServiceId,
+        service_version: impl Into<ServiceVersionRequirement>,
+        job_data: impl IntoIterator<Item = Vec<u8>>,
+        priority: Option<Priority>,
+        labels: Labels,
+    ) -> Result<Response<ResponseToClientJobsSubmit>, Status> {
+        let (service_version, service_version_constraint) = service_version.into().to_submission();
+        let request = |job_data, job_data_continues| RequestFromClientJobsSubmit {
+            session_token: self.session_token.clone(),
+            service_id,
+            service_version,
+            job_data,
+            priority,
+            service_version_constraint: service_version_constraint.clone(),
+            labels: labels.clone(),
+            job_data_continues,
+        };
+        let mut requests: Vec<RequestFromClientJobsSubmit> = vec![];
+        // The number of bytes in the last request, if more jobs may be added to it.
+        let mut open_batch_size: Option<usize> = None;
+
+        // Split the jobs into batches of a limited number of jobs and bytes.
+        for job_data in job_data {
+            // The job data is too large for a batch. Send it in chunks, each in its own message.
+            if job_data.len() > DATA_CHUNK_SIZE {
+                let mut chunks = split_into_chunks(job_data).into_iter().peekable();
+
+                while let Some(chunk) = chunks.next() {
+                    requests.push(request(vec![chunk], chunks.peek().is_some()));
+                }
+
+                open_batch_size = None;
+                continue;
+            }
+
+            match (requests.last_mut(), open_batch_size) {
+                (Some(last_request), Some(batch_size))
+                    if last_request.job_data.len() < JOB_BATCH_SIZE
+                        && batch_size + job_data.len() <= DATA_CHUNK_SIZE =>
+                {
+                    open_batch_size = Some(batch_size + job_data.len());
+                    last_request.job_data.push(job_data);
+                }
+                _ => {
+                    open_batch_size = Some(job_data.len());
+                    requests.push(request(vec![job_data], false));
+                }
+            }
+        }
+
+        self.grid_client
+            .client_stream_jobs(Request::new(tokio_stream::iter(requests)))
+            .await
+    }
+
+    /// Returns a stream of the data of the given job, which is leased to this worker, in chunks.
+    pub async fn worker_download_job(
+        &mut self,
+        job_id: JobId,
+    ) -> Result<Response<Streaming<Job>>, Status> {
+        self.grid_client
+            .worker_download_job(Request::new(RequestFromWorkerJobDownload {
+                session_token: self.session_token.clone(),
+                job_id,
+            }))
+            .await
+    }
+
+    /// Sends the given result of a job that is leased to this worker. Large result data is uploaded
+    /// in chunks, see `worker_upload_result()`.
     pub async fn worker_submit_result(
         &mut self,
         result: grid_server_interface::Result,
     ) -> Result<Response<ResponseToWorkerResultSubmit>, Status> {
+        // The result data is too large for a single message.
+        if result.result_data.len() > DATA_CHUNK_SIZE {
+            return self.worker_upload_result(result).await;
+        }
+
         self.grid_client
             .worker_submit_result(Request::new(RequestFromWorkerResultSubmit {
-                client_id: self.client_id,
+                session_token: self.session_token.clone(),
                 result: Some(result),
             }))
             .await
     }
 
+    /// Sends the given result like `worker_submit_result()`, but uploads the result data in
+    /// chunks, so that there is no limit on its size other than the grid server's.
+    pub async fn worker_upload_result(
+        &mut self,
+        result: grid_server_interface::Result,
+    ) -> Result<Response<ResponseToWorkerResultSubmit>, Status> {
+        let grid_server_interface::Result {
+            job_id,
+            result_data,
+            status,
+            error_message,
+            ..
+        } = result;
+
+        let requests: Vec<RequestFromWorkerResultSubmit> = split_into_chunks(result_data)
+            .into_iter()
+            .map(|result_data| RequestFromWorkerResultSubmit {
+                session_token: self.session_token.clone(),
+                result: Some(grid_server_interface::Result {
+                    job_id,
+                    result_data,
+                    status,
+                    error_message: error_message.clone(),
+                    labels: Labels::new(),
+                    result_data_size: 0,
+                }),
+            })
+            .collect();
+
+        self.grid_client
+            .worker_upload_result(Request::new(tokio_stream::iter(requests)))
+            .await
+    }
+
+    /// Extends the leases of the given jobs, which are still being processed by this worker.
+    pub async fn worker_extend_lease(
+        &mut self,
+        job_ids: Vec<JobId>,
+    ) -> Result<Response<ResponseToWorkerLeaseExtend>, Status> {
+        self.grid_client
+            .worker_extend_lease(Request::new(RequestFromWorkerLeaseExtend {
+                session_token: self.session_token.clone(),
+                job_ids,
+            }))
+            .await
+    }
+
+    /// Sends the given result, if any, and fetches up to the given number of jobs. If there is no
+    /// job, the server waits up to the given duration for one to be queued.
     ///
+    /// Large result data is uploaded and large job data is downloaded in chunks beforehand.
     pub async fn worker_server_exchange(
         &mut self,
         service_id: ServiceId,
         service_version: ServiceVersion,
         result_from_worker: Option<grid_server_interface::Result>,
+        wait_duration: Duration,
+        job_count: u32,
     ) -> Result<Response<ResponseToWorkerExchange>, Status> {
-        self.grid_client
+        // The result data is too large for a single message.
+        let result_from_worker = match result_from_worker {
+            Some(result) if result.result_data.len() > DATA_CHUNK_SIZE => {
+                self.worker_upload_result(result).await?;
+                None
+            }
+            result_from_worker => result_from_worker,
+        };
+
+        let mut response = self
+            .grid_client
             .worker_server_exchange(Request::new(RequestFromWorkerExchange {
-                client_id: self.client_id,
+                session_token: self.session_token.clone(),
                 query_job_from_server: Some(JobQuery {
                     service_id,
                     service_version,
+                    job_count,
                 }),
                 result_from_worker,
+                wait_milliseconds: wait_duration.as_millis() as u64,
             }))
-            .await
+            .await?;
+
+        // Download the job data that did not fit into the response.
+        let response_ref = response.get_mut();
+
+        for job in response_ref
+            .job
+            .iter_mut()
+            .chain(response_ref.prefetched_jobs.iter_mut())
+        {
+            if job.job_data_size > 0 {
+                self.download_job_data(job).await?;
+            }
+        }
+
+        Ok(response)
+    }
+
+    /// Downloads the data of the given job, which is leased to this worker, into the job.
+    async fn download_job_data(&mut self, job: &mut Job) -> Result<(), Status> {
+        let mut job_stream = self.worker_download_job(job.job_id).await?.into_inner();
+
+        job.job_data = Vec::with_capacity(job.job_data_size as usize);
+
+        while let Some(chunk) = job_stream.message().await? {
+            job.job_data.extend(chunk.job_data);
+        }
+
+        job.job_data_size = 0;
+
+        Ok(())
     }
 }
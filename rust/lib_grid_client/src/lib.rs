@@ -1,5 +1,8 @@
+// The clients return the `tonic::Status` of the RPCs, which is large.
+#![allow(clippy::result_large_err)]
+
 mod asynchronous;
 mod synchronous;
 
-pub use asynchronous::{connect_async_grid_client, AsyncGridClient};
+pub use asynchronous::{connect_async_grid_client, AsyncGridClient, ResultStream};
 pub use synchronous::{connect_sync_grid_client, SyncGridClient};
@@ -1,27 +1,41 @@
 use crate::{connect_async_grid_client, AsyncGridClient};
 use grid_server_interface::{
-    ResponseToClientJobSubmit, ResponseToClientResultFetch, ResponseToWorkerExchange,
-    ResponseToWorkerResultSubmit, ServiceId, ServiceVersion,
+    ClientRole, CompressionEncoding, JobId, Labels, Priority, ResponseToClientJobCancel,
+    ResponseToClientJobStatusGet, ResponseToClientJobSubmit, ResponseToClientJobTraceGet,
+    ResponseToClientJobsSubmit, ResponseToClientResultFetch, ResponseToWorkerExchange,
+    ResponseToWorkerLeaseExtend, ResponseToWorkerResultSubmit, ServiceId, ServiceVersion,
+    ServiceVersionRequirement, TlsPaths,
 };
+use std::time::Duration;
 use tokio::runtime::{Builder, Runtime};
 use tonic::{Response, Status};
 
-///
+/// A blocking client of the grid server, which runs an `AsyncGridClient` on its own runtime.
 pub struct SyncGridClient {
     async_grid_client: AsyncGridClient,
     async_runtime: Runtime,
 }
 
-///
+/// Connects to the grid server like `connect_async_grid_client()`.
 pub fn connect_sync_grid_client(
     server_address: &str,
     client_id: String,
+    role: ClientRole,
+    role_secret: Option<String>,
+    compression: Option<CompressionEncoding>,
+    tls_paths: &TlsPaths,
 ) -> Result<SyncGridClient, Box<dyn std::error::Error>> {
     let async_runtime = Builder::new_multi_thread().enable_all().build()?;
 
     // Connect the grid client.
-    let async_grid_client =
-        async_runtime.block_on(connect_async_grid_client(server_address, client_id))?;
+    let async_grid_client = async_runtime.block_on(connect_async_grid_client(
+        server_address,
+        client_id,
+        role,
+        role_secret,
+        compression,
+        tls_paths,
+    ))?;
 
     Ok(SyncGridClient {
         async_grid_client,
@@ -30,30 +44,111 @@ pub fn connect_sync_grid_client(
 }
 
 impl SyncGridClient {
-    ///
+    /// Withdraws the given jobs like `AsyncGridClient::client_cancel_job()`.
+    pub fn client_cancel_job(
+        &mut self,
+        job_ids: Vec<JobId>,
+        label_filter: Labels,
+    ) -> Result<Response<ResponseToClientJobCancel>, Status> {
+        self.async_runtime.block_on(
+            self.async_grid_client
+                .client_cancel_job(job_ids, label_filter),
+        )
+    }
+
+    /// Returns the results that arrived for this client like
+    /// `AsyncGridClient::client_fetch_results()`.
     pub fn client_fetch_results(
         &mut self,
+        label_filter: Labels,
     ) -> Result<Response<ResponseToClientResultFetch>, Status> {
         self.async_runtime
-            .block_on(self.async_grid_client.client_fetch_results())
+            .block_on(self.async_grid_client.client_fetch_results(label_filter))
+    }
+
+    /// Returns the status of the given jobs like `AsyncGridClient::client_get_job_status()`.
+    pub fn client_get_job_status(
+        &mut self,
+        job_ids: Vec<JobId>,
+        label_filter: Labels,
+    ) -> Result<Response<ResponseToClientJobStatusGet>, Status> {
+        self.async_runtime.block_on(
+            self.async_grid_client
+                .client_get_job_status(job_ids, label_filter),
+        )
     }
 
-    ///
+    /// Returns the lifecycle of the given jobs, which were submitted by this client.
+    pub fn client_get_job_trace(
+        &mut self,
+        job_ids: Vec<JobId>,
+    ) -> Result<Response<ResponseToClientJobTraceGet>, Status> {
+        self.async_runtime
+            .block_on(self.async_grid_client.client_get_job_trace(job_ids))
+    }
+
+    /// Submits the given job for an exact service version or any version that meets the given
+    /// requirement. Jobs with a higher priority are dispatched first, the priority is 0 if not
+    /// given. The given labels are passed to the worker and returned with the result.
     pub fn client_submit_job(
         &mut self,
         service_id: ServiceId,
-        service_version: ServiceVersion,
+        service_version: impl Into<ServiceVersionRequirement>,
         job_data: Vec<u8>,
+        priority: Option<Priority>,
+        labels: Labels,
     ) -> Result<Response<ResponseToClientJobSubmit>, Status> {
         self.async_runtime
             .block_on(self.async_grid_client.client_submit_job(
                 service_id,
                 service_version,
                 job_data,
+                priority,
+                labels,
             ))
     }
 
-    ///
+    /// Submits the given job like `client_submit_job()`, but uploads the job data in chunks, so
+    /// that there is no limit on its size other than the grid server's.
+    pub fn client_upload_job(
+        &mut self,
+        service_id: ServiceId,
+        service_version: impl Into<ServiceVersionRequirement>,
+        job_data: Vec<u8>,
+        priority: Option<Priority>,
+        labels: Labels,
+    ) -> Result<Response<ResponseToClientJobSubmit>, Status> {
+        self.async_runtime
+            .block_on(self.async_grid_client.client_upload_job(
+                service_id,
+                service_version,
+                job_data,
+                priority,
+                labels,
+            ))
+    }
+
+    /// Submits the given jobs for the same service, priority and labels and returns their job
+    /// IDs, in order.
+    pub fn client_submit_jobs(
+        &mut self,
+        service_id: ServiceId,
+        service_version: impl Into<ServiceVersionRequirement>,
+        job_data: impl IntoIterator<Item = Vec<u8>>,
+        priority: Option<Priority>,
+        labels: Labels,
+    ) -> Result<Response<ResponseToClientJobsSubmit>, Status> {
+        self.async_runtime
+            .block_on(self.async_grid_client.client_submit_jobs(
+                service_id,
+                service_version,
+                job_data,
+                priority,
+                labels,
+            ))
+    }
+
+    /// Sends the given result like `AsyncGridClient::worker_submit_result()`.
     pub fn worker_submit_result(
         &mut self,
         result: grid_server_interface::Result,
@@ -62,18 +157,42 @@ impl SyncGridClient {
             .block_on(self.async_grid_client.worker_submit_result(result))
     }
 
-    ///
+    /// Sends the given result like `worker_submit_result()`, but uploads the result data in
+    /// chunks, so that there is no limit on its size other than the grid server's.
+    pub fn worker_upload_result(
+        &mut self,
+        result: grid_server_interface::Result,
+    ) -> Result<Response<ResponseToWorkerResultSubmit>, Status> {
+        self.async_runtime
+            .block_on(self.async_grid_client.worker_upload_result(result))
+    }
+
+    /// Extends the leases of the given jobs, which are still being processed by this worker.
+    pub fn worker_extend_lease(
+        &mut self,
+        job_ids: Vec<JobId>,
+    ) -> Result<Response<ResponseToWorkerLeaseExtend>, Status> {
+        self.async_runtime
+            .block_on(self.async_grid_client.worker_extend_lease(job_ids))
+    }
+
+    /// Sends the given result, if any, and fetches up to the given number of jobs. If there is no
+    /// job, the server waits up to the given duration for one to be queued.
     pub fn worker_server_exchange(
         &mut self,
         service_id: ServiceId,
         service_version: ServiceVersion,
         result_from_worker: Option<grid_server_interface::Result>,
+        wait_duration: Duration,
+        job_count: u32,
     ) -> Result<Response<ResponseToWorkerExchange>, Status> {
         self.async_runtime
             .block_on(self.async_grid_client.worker_server_exchange(
                 service_id,
                 service_version,
                 result_from_worker,
+                wait_duration,
+                job_count,
             ))
     }
 }
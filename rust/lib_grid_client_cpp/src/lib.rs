@@ -2,18 +2,28 @@ mod asynchronous;
 mod synchronous;
 
 use grid_client::SyncGridClient;
+use grid_server_interface::ClientRole;
 
 /// A wrapper to avoid `cxx`'s limitation that types need to be implemented in this crate.
-struct SyncGridClientWrapper(SyncGridClient);
+/// The bridge does not expose any methods of the client yet.
+struct SyncGridClientWrapper(#[allow(dead_code)] SyncGridClient);
 
-///
+/// Connects to the grid server at the given address and registers as a submitting client with
+/// the given description.
 fn connect_sync_grid_client(
     server_address: &str,
     client_id: String,
 ) -> Result<Box<SyncGridClientWrapper>, Box<dyn std::error::Error>> {
     // Wrap the `SyncGridClient` in a `SyncGridClientWrapper`.
     Ok(Box::new(SyncGridClientWrapper(
-        grid_client::connect_sync_grid_client(server_address, client_id)?,
+        grid_client::connect_sync_grid_client(
+            server_address,
+            client_id,
+            ClientRole::Submitter,
+            None,
+            None,
+            &Default::default(),
+        )?,
     )))
 }
 
@@ -19,7 +19,7 @@ impl AsyncGridClient {
         /*
         Ok(AsyncGridClient {
             async_grid_client: pyo3_asyncio::tokio::future_into_py_with_locals(
-                connect_async_grid_client(server_address, client_id)
+                connect_async_grid_client(server_address, client_id, ClientRole::Submitter, None, None, &TlsPaths::default())
                     .await
                     .map_err(|error| {
                         PyTypeError::new_err(format!("Can not connect to the server: {}", error))
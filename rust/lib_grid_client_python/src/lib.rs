@@ -1,22 +1,52 @@
+// The `#[pymethods]` macro of pyo3 0.19 defines `impl` blocks inside of functions.
+#![allow(non_local_definitions)]
+
 mod asynchronous;
 mod synchronous;
 
 use crate::synchronous::SyncGridClient;
-use grid_server_interface::JobId;
+use grid_server_interface::{
+    ClientId, JobId, JobState, Labels, Priority, ResultStatus, ServiceVersion,
+    ServiceVersionRequirement,
+};
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 use pyo3::types::PyBytes;
 
+/// Returns the service versions that may process a job from the given Python object, which is
+/// either an exact version or a string like ">=41", "latest" or an alias.
+fn service_version_requirement_from_py(
+    service_version: &PyAny,
+) -> PyResult<ServiceVersionRequirement> {
+    if let Ok(service_version) = service_version.extract::<ServiceVersion>() {
+        return Ok(ServiceVersionRequirement::Exactly(service_version));
+    }
+
+    service_version
+        .extract::<&str>()?
+        .parse()
+        .map_err(PyValueError::new_err)
+}
+
 #[pyclass]
 struct Job {
     job_data: Vec<u8>,
     job_id: JobId,
+    labels: Labels,
+    lease_milliseconds: u64,
 }
 
-///
-fn job_from_interface_job(interface_job: grid_server_interface::Job) -> Job {
+/// Converts the given job from the server, which is leased for the given milliseconds, into a
+/// Python job.
+fn job_from_interface_job(
+    interface_job: grid_server_interface::Job,
+    lease_milliseconds: u64,
+) -> Job {
     Job {
         job_data: interface_job.job_data,
         job_id: interface_job.job_id,
+        labels: interface_job.labels,
+        lease_milliseconds,
     }
 }
 
@@ -31,6 +61,17 @@ impl Job {
     fn job_id(&self) -> PyResult<JobId> {
         Ok(self.job_id)
     }
+
+    /// The labels the job was submitted with.
+    #[getter]
+    fn labels(&self) -> PyResult<Labels> {
+        Ok(self.labels.clone())
+    }
+
+    #[getter]
+    fn lease_milliseconds(&self) -> PyResult<u64> {
+        Ok(self.lease_milliseconds)
+    }
 }
 
 // =================================================================================================
@@ -38,55 +79,234 @@ impl Job {
 #[pyclass]
 #[derive(Clone)]
 struct Result {
+    error_message: Option<String>,
     job_id: JobId,
+    labels: Labels,
     result_data: Vec<u8>,
 }
 
 #[pymethods]
 impl Result {
+    /// Creates a result. Passing an error message reports that the job failed.
     #[new]
-    fn new(job_id: JobId, result_data: Vec<u8>) -> Self {
+    #[pyo3(signature = (job_id, result_data, error_message=None))]
+    fn new(job_id: JobId, result_data: Vec<u8>, error_message: Option<String>) -> Self {
         Result {
+            error_message,
             job_id,
+            labels: Labels::new(),
             result_data,
         }
     }
 
+    #[getter]
+    fn error_message(&self) -> PyResult<Option<String>> {
+        Ok(self.error_message.clone())
+    }
+
+    #[getter]
+    fn failed(&self) -> PyResult<bool> {
+        Ok(self.error_message.is_some())
+    }
+
     #[getter]
     fn job_id(&self) -> PyResult<JobId> {
         Ok(self.job_id)
     }
 
+    /// The labels of the job, if the result was fetched.
+    #[getter]
+    fn labels(&self) -> PyResult<Labels> {
+        Ok(self.labels.clone())
+    }
+
     #[getter]
     fn result_data(&self) -> PyResult<&[u8]> {
         Ok(&self.result_data)
     }
 }
 
-///
+/// Converts the given Python result into a result for the server.
 fn interface_result_from_result(result: Result) -> grid_server_interface::Result {
+    let status = if result.error_message.is_some() {
+        ResultStatus::Failed
+    } else {
+        ResultStatus::Succeeded
+    };
+
     grid_server_interface::Result {
         job_id: result.job_id,
         result_data: result.result_data,
+        status: status.into(),
+        error_message: result.error_message.unwrap_or_default(),
+        labels: Labels::new(),
+        result_data_size: 0,
     }
 }
 
-///
+/// Converts the given result from the server into a Python result.
 fn result_from_interface_result(interface_result: grid_server_interface::Result) -> Result {
+    let error_message = match interface_result.status() {
+        ResultStatus::Failed => Some(interface_result.error_message),
+        ResultStatus::Succeeded => None,
+    };
+
     Result {
+        error_message,
         job_id: interface_result.job_id,
+        labels: interface_result.labels,
         result_data: interface_result.result_data,
     }
 }
 
 // =================================================================================================
 
+#[pyclass]
+struct JobStatus {
+    job_id: JobId,
+    labels: Labels,
+    priority: Option<Priority>,
+    queue_position: Option<u64>,
+    state: &'static str,
+    worker_client_id: Option<ClientId>,
+}
+
+/// Converts the given job status from the server into a Python job status.
+fn job_status_from_interface_job_status(
+    interface_job_status: grid_server_interface::JobStatus,
+) -> JobStatus {
+    let state = match interface_job_status.state() {
+        JobState::Unknown => "unknown",
+        JobState::Queued => "queued",
+        JobState::Dispatched => "dispatched",
+        JobState::Completed => "completed",
+        JobState::Failed => "failed",
+        JobState::Cancelled => "cancelled",
+    };
+
+    JobStatus {
+        job_id: interface_job_status.job_id,
+        labels: interface_job_status.labels,
+        priority: interface_job_status.priority,
+        queue_position: interface_job_status.queue_position,
+        state,
+        worker_client_id: interface_job_status.worker_client_id,
+    }
+}
+
+#[pymethods]
+impl JobStatus {
+    #[getter]
+    fn job_id(&self) -> PyResult<JobId> {
+        Ok(self.job_id)
+    }
+
+    /// The labels the job was submitted with.
+    #[getter]
+    fn labels(&self) -> PyResult<Labels> {
+        Ok(self.labels.clone())
+    }
+
+    /// The priority of the job, if it is queued or dispatched.
+    #[getter]
+    fn priority(&self) -> PyResult<Option<Priority>> {
+        Ok(self.priority)
+    }
+
+    /// The number of jobs ahead of the job in its queue, if it is queued.
+    #[getter]
+    fn queue_position(&self) -> PyResult<Option<u64>> {
+        Ok(self.queue_position)
+    }
+
+    /// One of "unknown", "queued", "dispatched", "completed", "failed" and "cancelled".
+    #[getter]
+    fn state(&self) -> PyResult<&'static str> {
+        Ok(self.state)
+    }
+
+    /// The grid worker processing the job, if it is dispatched.
+    #[getter]
+    fn worker_client_id(&self) -> PyResult<Option<ClientId>> {
+        Ok(self.worker_client_id)
+    }
+}
+
+// =================================================================================================
+
+#[pyclass]
+struct JobTrace {
+    dispatches: Vec<(ClientId, String)>,
+    job_id: JobId,
+    result_fetched_at: Option<String>,
+    result_received_at: Option<String>,
+    submitted_at: Option<String>,
+}
+
+/// Returns the given time, unless it is empty because the job did not get there yet.
+fn non_empty(time: String) -> Option<String> {
+    Some(time).filter(|time| !time.is_empty())
+}
+
+/// Converts the given job trace, turning empty times into `None`.
+fn job_trace_from_interface_job_trace(
+    interface_job_trace: grid_server_interface::JobTrace,
+) -> JobTrace {
+    JobTrace {
+        dispatches: interface_job_trace
+            .dispatches
+            .into_iter()
+            .map(|dispatch| (dispatch.worker_client_id, dispatch.dispatched_at))
+            .collect(),
+        job_id: interface_job_trace.job_id,
+        result_fetched_at: non_empty(interface_job_trace.result_fetched_at),
+        result_received_at: non_empty(interface_job_trace.result_received_at),
+        submitted_at: non_empty(interface_job_trace.submitted_at),
+    }
+}
+
+#[pymethods]
+impl JobTrace {
+    /// Every dispatch of the job as a tuple of the worker client ID and the time, in order.
+    #[getter]
+    fn dispatches(&self) -> PyResult<Vec<(ClientId, String)>> {
+        Ok(self.dispatches.clone())
+    }
+
+    #[getter]
+    fn job_id(&self) -> PyResult<JobId> {
+        Ok(self.job_id)
+    }
+
+    /// The time the result was fetched by or streamed to the client, if it was.
+    #[getter]
+    fn result_fetched_at(&self) -> PyResult<Option<String>> {
+        Ok(self.result_fetched_at.clone())
+    }
+
+    /// The time the grid server received the job's final result, if it did.
+    #[getter]
+    fn result_received_at(&self) -> PyResult<Option<String>> {
+        Ok(self.result_received_at.clone())
+    }
+
+    /// The time the job was submitted, unless the job is unknown.
+    #[getter]
+    fn submitted_at(&self) -> PyResult<Option<String>> {
+        Ok(self.submitted_at.clone())
+    }
+}
+
+// =================================================================================================
+
 /// A Python module implemented in Rust. The name of this function must match
 /// the `lib.name` setting in the `Cargo.toml`, else Python will not be able to
 /// import the module.
 #[pymodule]
 fn grid(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
     m.add_class::<Job>()?;
+    m.add_class::<JobStatus>()?;
+    m.add_class::<JobTrace>()?;
     m.add_class::<Result>()?;
     m.add_class::<SyncGridClient>()?;
     Ok(())
@@ -1,10 +1,17 @@
 use crate::{
-    interface_result_from_result, job_from_interface_job, result_from_interface_result, Job, Result,
+    interface_result_from_result, job_from_interface_job, job_status_from_interface_job_status,
+    job_trace_from_interface_job_trace, result_from_interface_result,
+    service_version_requirement_from_py, Job, JobStatus, JobTrace, Result,
 };
 use grid_client::connect_sync_grid_client;
-use grid_server_interface::{JobId, ServiceId, ServiceVersion};
+use grid_server_interface::{
+    client_role_from_str, compression_encoding_from_str, JobId, Labels, Priority, ServiceId,
+    ServiceVersion, TlsPaths,
+};
 use pyo3::exceptions::PyTypeError;
 use pyo3::prelude::*;
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[pyclass]
 pub(crate) struct SyncGridClient {
@@ -13,18 +20,94 @@ pub(crate) struct SyncGridClient {
 
 #[pymethods]
 impl SyncGridClient {
+    /// Connects to the grid server as a "submitter", "worker" or "controller". The role secret is
+    /// required if the grid server is given a secret for the role. The requests are compressed
+    /// with "gzip" or "zstd", if given.
+    /// The connection uses TLS if a CA certificate is given, and mutual TLS if also the client's
+    /// certificate and key are given.
     #[new]
-    pub(crate) fn new(server_address: &str, client_id: String) -> PyResult<Self> {
+    #[pyo3(signature = (
+        server_address,
+        client_id,
+        role="submitter",
+        role_secret=None,
+        compression=None,
+        tls_ca_certificate_path=None,
+        tls_certificate_path=None,
+        tls_key_path=None,
+    ))]
+    // Python passes the options as keyword arguments.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        server_address: &str,
+        client_id: String,
+        role: &str,
+        role_secret: Option<String>,
+        compression: Option<&str>,
+        tls_ca_certificate_path: Option<PathBuf>,
+        tls_certificate_path: Option<PathBuf>,
+        tls_key_path: Option<PathBuf>,
+    ) -> PyResult<Self> {
+        let role = client_role_from_str(role).map_err(PyTypeError::new_err)?;
+
+        let compression = match compression {
+            Some(compression) => {
+                compression_encoding_from_str(compression).map_err(PyTypeError::new_err)?
+            }
+            None => None,
+        };
+
         Ok(SyncGridClient {
-            sync_grid_client: connect_sync_grid_client(server_address, client_id).map_err(
-                |error| PyTypeError::new_err(format!("Can not connect to the server: {}", error)),
-            )?,
+            sync_grid_client: connect_sync_grid_client(
+                server_address,
+                client_id,
+                role,
+                role_secret,
+                compression,
+                &TlsPaths {
+                    ca_certificate_path: tls_ca_certificate_path,
+                    certificate_path: tls_certificate_path,
+                    key_path: tls_key_path,
+                },
+            )
+            .map_err(|error| {
+                PyTypeError::new_err(format!("Can not connect to the server: {}", error))
+            })?,
         })
     }
 
-    ///
-    pub(crate) fn client_fetch_results(&mut self) -> PyResult<Vec<Result>> {
-        match self.sync_grid_client.client_fetch_results() {
+    /// Withdraws the given jobs and returns the IDs of the jobs that were actually cancelled. With
+    /// a label filter, only the jobs with all of its labels are cancelled, and without job IDs,
+    /// every such job of this client is.
+    #[pyo3(signature = (job_ids, label_filter=None))]
+    pub(crate) fn client_cancel_job(
+        &mut self,
+        job_ids: Vec<JobId>,
+        label_filter: Option<Labels>,
+    ) -> PyResult<Vec<JobId>> {
+        match self
+            .sync_grid_client
+            .client_cancel_job(job_ids, label_filter.unwrap_or_default())
+        {
+            Ok(job_cancel_response) => Ok(job_cancel_response.into_inner().cancelled_job_ids),
+            Err(error) => Err(PyTypeError::new_err(format!(
+                "Could not cancel the jobs: {}",
+                error
+            ))),
+        }
+    }
+
+    /// Fetches the results of this client's jobs. With a label filter, only the results of the
+    /// jobs with all of its labels are fetched, and the others are kept for later.
+    #[pyo3(signature = (label_filter=None))]
+    pub(crate) fn client_fetch_results(
+        &mut self,
+        label_filter: Option<Labels>,
+    ) -> PyResult<Vec<Result>> {
+        match self
+            .sync_grid_client
+            .client_fetch_results(label_filter.unwrap_or_default())
+        {
             // TODO: Can we move the data instead of cloning?
             Ok(result_fetch_response) => Ok(result_fetch_response
                 .get_ref()
@@ -40,17 +123,67 @@ impl SyncGridClient {
         }
     }
 
-    ///
+    /// Returns the status of the given jobs, in the order of the given job IDs. With a label
+    /// filter, only the jobs with all of its labels are returned, and without job IDs, every such
+    /// job of this client is.
+    #[pyo3(signature = (job_ids, label_filter=None))]
+    pub(crate) fn client_get_job_status(
+        &mut self,
+        job_ids: Vec<JobId>,
+        label_filter: Option<Labels>,
+    ) -> PyResult<Vec<JobStatus>> {
+        match self
+            .sync_grid_client
+            .client_get_job_status(job_ids, label_filter.unwrap_or_default())
+        {
+            Ok(job_status_response) => Ok(job_status_response
+                .into_inner()
+                .job_status
+                .into_iter()
+                .map(job_status_from_interface_job_status)
+                .collect()),
+            Err(error) => Err(PyTypeError::new_err(format!(
+                "Could not get the job status from the server: {}",
+                error
+            ))),
+        }
+    }
+
+    /// Returns the lifecycle of the given jobs, in the order of the given job IDs.
+    pub(crate) fn client_get_job_trace(&mut self, job_ids: Vec<JobId>) -> PyResult<Vec<JobTrace>> {
+        match self.sync_grid_client.client_get_job_trace(job_ids) {
+            Ok(job_trace_response) => Ok(job_trace_response
+                .into_inner()
+                .job_traces
+                .into_iter()
+                .map(job_trace_from_interface_job_trace)
+                .collect()),
+            Err(error) => Err(PyTypeError::new_err(format!(
+                "Could not get the job traces from the server: {}",
+                error
+            ))),
+        }
+    }
+
+    /// Submits the given job for an exact service version or for a string like ">=41", "latest"
+    /// or an alias. Jobs with a higher priority are dispatched first. The labels are a dict of
+    /// strings by which the job can be filtered later.
+    #[pyo3(signature = (service_id, service_version, job_data, priority=None, labels=None))]
     pub(crate) fn client_submit_job(
         &mut self,
         service_id: ServiceId,
-        service_version: ServiceVersion,
+        service_version: &PyAny,
         job_data: Vec<u8>,
+        priority: Option<Priority>,
+        labels: Option<Labels>,
     ) -> PyResult<Option<JobId>> {
-        match self
-            .sync_grid_client
-            .client_submit_job(service_id, service_version, job_data)
-        {
+        match self.sync_grid_client.client_submit_job(
+            service_id,
+            service_version_requirement_from_py(service_version)?,
+            job_data,
+            priority,
+            labels.unwrap_or_default(),
+        ) {
             Ok(job_submit_response) => Ok(job_submit_response.get_ref().job_id),
             Err(error) => Err(PyTypeError::new_err(format!(
                 "Can not submit job to the server: {}",
@@ -59,24 +192,67 @@ impl SyncGridClient {
         }
     }
 
-    ///
+    /// Submits the jobs of the given iterable for the same service, priority and labels and
+    /// returns their job IDs, in order. The list is empty if the server does not accept new jobs.
+    /// The service version is given like for `client_submit_job()`.
+    #[pyo3(signature = (service_id, service_version, job_data, priority=None, labels=None))]
+    pub(crate) fn client_submit_jobs(
+        &mut self,
+        service_id: ServiceId,
+        service_version: &PyAny,
+        job_data: &PyAny,
+        priority: Option<Priority>,
+        labels: Option<Labels>,
+    ) -> PyResult<Vec<JobId>> {
+        let job_data = job_data
+            .iter()?
+            .map(|job_data| job_data?.extract::<Vec<u8>>())
+            .collect::<PyResult<Vec<_>>>()?;
+
+        match self.sync_grid_client.client_submit_jobs(
+            service_id,
+            service_version_requirement_from_py(service_version)?,
+            job_data,
+            priority,
+            labels.unwrap_or_default(),
+        ) {
+            Ok(jobs_submit_response) => Ok(jobs_submit_response.into_inner().job_ids),
+            Err(error) => Err(PyTypeError::new_err(format!(
+                "Can not submit jobs to the server: {}",
+                error
+            ))),
+        }
+    }
+
+    /// Sends the given result, if any, and fetches a job. If there is no job, the server waits up
+    /// to the given milliseconds for one to be queued. Returns the job, if any, together with the
+    /// IDs of the jobs of this worker that were cancelled and should be abandoned.
+    #[pyo3(signature = (service_id, service_version, result_from_worker, wait_milliseconds=0))]
     pub(crate) fn worker_server_exchange(
         &mut self,
         service_id: ServiceId,
         service_version: ServiceVersion,
         result_from_worker: Option<Result>,
-    ) -> PyResult<Option<Job>> {
+        wait_milliseconds: u64,
+    ) -> PyResult<(Option<Job>, Vec<JobId>)> {
         match self.sync_grid_client.worker_server_exchange(
             service_id,
             service_version,
             result_from_worker.map(interface_result_from_result),
+            Duration::from_millis(wait_milliseconds),
+            1,
         ) {
-            // TODO: Can we move the data instead of cloning?
-            Ok(job_fetch_response) => Ok(job_fetch_response
-                .get_ref()
-                .clone()
-                .job
-                .map(job_from_interface_job)),
+            Ok(job_fetch_response) => {
+                let job_fetch_response = job_fetch_response.into_inner();
+                let lease_milliseconds = job_fetch_response.lease_milliseconds;
+
+                Ok((
+                    job_fetch_response
+                        .job
+                        .map(|job| job_from_interface_job(job, lease_milliseconds)),
+                    job_fetch_response.cancelled_job_ids,
+                ))
+            }
             Err(error) => Err(PyTypeError::new_err(format!(
                 "Could not fetch a job from the server: {}",
                 error
@@ -84,7 +260,18 @@ impl SyncGridClient {
         }
     }
 
-    ///
+    /// Extends the leases of the given jobs and returns the IDs of the jobs whose lease is lost.
+    pub(crate) fn worker_extend_lease(&mut self, job_ids: Vec<JobId>) -> PyResult<Vec<JobId>> {
+        match self.sync_grid_client.worker_extend_lease(job_ids) {
+            Ok(lease_extend_response) => Ok(lease_extend_response.into_inner().lost_job_ids),
+            Err(error) => Err(PyTypeError::new_err(format!(
+                "Could not extend the job leases: {}",
+                error
+            ))),
+        }
+    }
+
+    /// Sends the given result of a job that is leased to this worker.
     pub(crate) fn worker_submit_result(&mut self, result: Result) -> PyResult<()> {
         match self
             .sync_grid_client
@@ -1,3 +1,11 @@
+mod role_secret;
+mod tls;
+
+use grid_server_interface::service_version_constraint::Constraint;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
 pub mod grid_server_interface {
     // Include the generated rust module.
     tonic::include_proto!("grid_server_interface");
@@ -9,6 +17,12 @@ pub type ClientId = u32;
 /// The job ID type.
 pub type JobId = u64;
 
+/// The key/value tags of a job.
+pub type Labels = HashMap<String, String>;
+
+/// The job priority type. Jobs with a higher priority are dispatched first.
+pub type Priority = i32;
+
 /// The raw byte data.
 pub type RawData = Vec<u8>;
 
@@ -18,6 +32,256 @@ pub type ServiceId = u32;
 /// The service version type.
 pub type ServiceVersion = u32;
 
+/// The maximum size of the job or result data in a single message. Larger data is uploaded and
+/// downloaded in chunks of this size.
+pub const DATA_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Splits the given job or result data into chunks of `DATA_CHUNK_SIZE` bytes. Empty data is
+/// returned as a single empty chunk, so that there is always a message to send.
+pub fn split_into_chunks(data: RawData) -> Vec<RawData> {
+    if data.is_empty() {
+        return vec![data];
+    }
+
+    data.chunks(DATA_CHUNK_SIZE)
+        .map(|chunk| chunk.to_vec())
+        .collect()
+}
+
+/// Returns whether the given labels contain every label of the given filter with the same value.
+/// Every job matches an empty filter.
+pub fn labels_match(labels: &Labels, label_filter: &Labels) -> bool {
+    label_filter
+        .iter()
+        .all(|(key, value)| labels.get(key) == Some(value))
+}
+
+/// The versions of a service that may process a job.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ServiceVersionRequirement {
+    /// Only the given version, e.g. "41".
+    Exactly(ServiceVersion),
+    /// The given version or a newer one, e.g. ">=41".
+    AtLeast(ServiceVersion),
+    /// At least the newest version that a grid worker asked jobs for, "latest". Unknown until a
+    /// grid worker asked for jobs.
+    Latest,
+    /// A requirement that is named on the grid server's command line, e.g. "stable".
+    Alias(String),
+}
+
+impl ServiceVersionRequirement {
+    /// Returns the requirement from the exact service version and the constraint of a job
+    /// submission.
+    pub fn from_submission(
+        service_version: ServiceVersion,
+        maybe_constraint: Option<&ServiceVersionConstraint>,
+    ) -> Self {
+        match maybe_constraint.and_then(|constraint| constraint.constraint.as_ref()) {
+            Some(Constraint::MinServiceVersion(min_service_version)) => {
+                ServiceVersionRequirement::AtLeast(*min_service_version)
+            }
+            Some(Constraint::Latest(_)) => ServiceVersionRequirement::Latest,
+            Some(Constraint::Alias(alias)) => ServiceVersionRequirement::Alias(alias.clone()),
+            None => ServiceVersionRequirement::Exactly(service_version),
+        }
+    }
+
+    /// Returns the exact service version and the constraint of a job submission.
+    pub fn to_submission(&self) -> (ServiceVersion, Option<ServiceVersionConstraint>) {
+        let constraint = match self {
+            ServiceVersionRequirement::Exactly(service_version) => return (*service_version, None),
+            ServiceVersionRequirement::AtLeast(min_service_version) => {
+                Constraint::MinServiceVersion(*min_service_version)
+            }
+            ServiceVersionRequirement::Latest => Constraint::Latest(true),
+            ServiceVersionRequirement::Alias(alias) => Constraint::Alias(alias.clone()),
+        };
+
+        (
+            0,
+            Some(ServiceVersionConstraint {
+                constraint: Some(constraint),
+            }),
+        )
+    }
+}
+
+impl From<ServiceVersion> for ServiceVersionRequirement {
+    fn from(service_version: ServiceVersion) -> Self {
+        ServiceVersionRequirement::Exactly(service_version)
+    }
+}
+
+impl FromStr for ServiceVersionRequirement {
+    type Err = String;
+
+    fn from_str(string: &str) -> std::result::Result<Self, Self::Err> {
+        let parse_service_version = |service_version: &str| {
+            service_version
+                .trim()
+                .parse()
+                .map_err(|error| format!("Invalid service version \"{service_version}\": {error}"))
+        };
+
+        match string.trim() {
+            "" => Err("The service version must not be empty".to_string()),
+            "latest" => Ok(ServiceVersionRequirement::Latest),
+            string if string.starts_with(|character: char| character.is_ascii_digit()) => Ok(
+                ServiceVersionRequirement::Exactly(parse_service_version(string)?),
+            ),
+            string => match string.strip_prefix(">=") {
+                Some(min_service_version) => Ok(ServiceVersionRequirement::AtLeast(
+                    parse_service_version(min_service_version)?,
+                )),
+                None => Ok(ServiceVersionRequirement::Alias(string.to_string())),
+            },
+        }
+    }
+}
+
+impl Display for ServiceVersionRequirement {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServiceVersionRequirement::Exactly(service_version) => {
+                write!(formatter, "{service_version}")
+            }
+            ServiceVersionRequirement::AtLeast(min_service_version) => {
+                write!(formatter, ">={min_service_version}")
+            }
+            ServiceVersionRequirement::Latest => write!(formatter, "latest"),
+            ServiceVersionRequirement::Alias(alias) => write!(formatter, "{alias}"),
+        }
+    }
+}
+
+/// Parses a client role, "submitter", "worker" or "controller".
+pub fn client_role_from_str(string: &str) -> std::result::Result<ClientRole, String> {
+    match string.trim() {
+        "submitter" => Ok(ClientRole::Submitter),
+        "worker" => Ok(ClientRole::Worker),
+        "controller" => Ok(ClientRole::Controller),
+        string => Err(format!(
+            "Unknown role \"{string}\", expected \"submitter\", \"worker\" or \"controller\""
+        )),
+    }
+}
+
+/// Parses the compression of the payloads sent to the other side of a connection, "gzip",
+/// "zstd" or "none".
+pub fn compression_encoding_from_str(
+    string: &str,
+) -> std::result::Result<Option<CompressionEncoding>, String> {
+    match string.trim() {
+        "gzip" => Ok(Some(CompressionEncoding::Gzip)),
+        "zstd" => Ok(Some(CompressionEncoding::Zstd)),
+        "none" => Ok(None),
+        string => Err(format!(
+            "Invalid compression \"{string}\", expected \"gzip\", \"zstd\" or \"none\""
+        )),
+    }
+}
+
 pub use grid_server_interface::grid_server_client::GridServerClient;
 pub use grid_server_interface::grid_server_server::{GridServer, GridServerServer};
 pub use grid_server_interface::*;
+pub use role_secret::{
+    read_role_secret, role_secrets_equal, take_role_secret_from_command_line_arguments,
+    ROLE_SECRET_USAGE,
+};
+pub use tls::{TlsPaths, TLS_USAGE};
+pub use tonic::codec::CompressionEncoding;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn service_version_requirements_are_parsed_and_displayed() {
+        for (string, service_version_requirement) in [
+            ("41", ServiceVersionRequirement::Exactly(41)),
+            (">=41", ServiceVersionRequirement::AtLeast(41)),
+            ("latest", ServiceVersionRequirement::Latest),
+            (
+                "stable",
+                ServiceVersionRequirement::Alias("stable".to_string()),
+            ),
+        ] {
+            assert_eq!(string.parse(), Ok(service_version_requirement.clone()));
+            assert_eq!(service_version_requirement.to_string(), string);
+        }
+
+        assert!("".parse::<ServiceVersionRequirement>().is_err());
+        assert!("41a".parse::<ServiceVersionRequirement>().is_err());
+        assert!(">=x".parse::<ServiceVersionRequirement>().is_err());
+    }
+
+    #[test]
+    fn service_version_requirements_survive_submissions() {
+        for service_version_requirement in [
+            ServiceVersionRequirement::Exactly(41),
+            ServiceVersionRequirement::AtLeast(41),
+            ServiceVersionRequirement::Latest,
+            ServiceVersionRequirement::Alias("stable".to_string()),
+        ] {
+            let (service_version, maybe_constraint) = service_version_requirement.to_submission();
+
+            assert_eq!(
+                ServiceVersionRequirement::from_submission(
+                    service_version,
+                    maybe_constraint.as_ref()
+                ),
+                service_version_requirement
+            );
+        }
+    }
+
+    #[test]
+    fn labels_match_filters_with_the_same_values() {
+        let labels = Labels::from([
+            ("batch".to_string(), "7".to_string()),
+            ("user".to_string(), "alice".to_string()),
+        ]);
+
+        assert!(labels_match(&labels, &Labels::new()));
+        assert!(labels_match(
+            &labels,
+            &Labels::from([("batch".to_string(), "7".to_string())])
+        ));
+        assert!(labels_match(&labels, &labels));
+        assert!(!labels_match(
+            &labels,
+            &Labels::from([("batch".to_string(), "8".to_string())])
+        ));
+        assert!(!labels_match(
+            &labels,
+            &Labels::from([("run".to_string(), "7".to_string())])
+        ));
+        assert!(!labels_match(
+            &Labels::new(),
+            &Labels::from([("batch".to_string(), "7".to_string())])
+        ));
+    }
+
+    #[test]
+    fn data_is_split_into_chunks_of_the_chunk_size() {
+        let data: RawData = (0..2 * DATA_CHUNK_SIZE + 1)
+            .map(|index| index as u8)
+            .collect();
+
+        let chunks = split_into_chunks(data.clone());
+
+        assert_eq!(
+            chunks.iter().map(Vec::len).collect::<Vec<_>>(),
+            [DATA_CHUNK_SIZE, DATA_CHUNK_SIZE, 1]
+        );
+        assert_eq!(chunks.concat(), data);
+
+        assert_eq!(split_into_chunks(vec![1; DATA_CHUNK_SIZE]).len(), 1);
+    }
+
+    #[test]
+    fn empty_data_is_a_single_empty_chunk() {
+        assert_eq!(split_into_chunks(vec![]), [Vec::<u8>::new()]);
+    }
+}
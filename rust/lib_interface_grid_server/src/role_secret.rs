@@ -0,0 +1,82 @@
+use std::fs::read_to_string;
+use std::path::Path;
+
+/// The usage of the role secret option on the command lines of the clients.
+pub const ROLE_SECRET_USAGE: &str = "[--role-secret-file <PATH>]";
+
+/// Returns the secret in the file at the given path, without the surrounding whitespace.
+///
+/// A grid server that is given a secret for a role only registers the clients with this role that
+/// present the same secret.
+pub fn read_role_secret(path: impl AsRef<Path>) -> Result<String, String> {
+    let path = path.as_ref();
+
+    let role_secret = read_to_string(path)
+        .map_err(|error| {
+            format!(
+                "Can not read the role secret \"{}\": {error}",
+                path.display()
+            )
+        })?
+        .trim()
+        .to_string();
+
+    if role_secret.is_empty() {
+        return Err(format!("The role secret \"{}\" is empty", path.display()));
+    }
+
+    Ok(role_secret)
+}
+
+/// Returns whether the given role secrets are equal. The time of the comparison does not depend on
+/// where they differ, so that a secret can not be guessed byte by byte.
+pub fn role_secrets_equal(role_secret: &str, other_role_secret: &str) -> bool {
+    let (role_secret, other_role_secret) = (role_secret.as_bytes(), other_role_secret.as_bytes());
+
+    role_secret.len() == other_role_secret.len()
+        && role_secret
+            .iter()
+            .zip(other_role_secret)
+            .fold(0, |difference, (byte, other_byte)| {
+                difference | (byte ^ other_byte)
+            })
+            == 0
+}
+
+/// Removes the role secret option and its path from the given command line arguments and returns
+/// the secret in the file, if the option is given.
+pub fn take_role_secret_from_command_line_arguments(
+    command_line_arguments: &mut Vec<String>,
+) -> Result<Option<String>, String> {
+    let mut role_secret = None;
+    let mut all_command_line_arguments = std::mem::take(command_line_arguments).into_iter();
+
+    while let Some(command_line_argument) = all_command_line_arguments.next() {
+        if command_line_argument != "--role-secret-file" {
+            command_line_arguments.push(command_line_argument);
+            continue;
+        }
+
+        let path = all_command_line_arguments
+            .next()
+            .ok_or_else(|| format!("Missing value for \"{command_line_argument}\""))?;
+
+        role_secret = Some(read_role_secret(path)?);
+    }
+
+    Ok(role_secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn role_secrets_are_only_equal_if_every_byte_is() {
+        assert!(role_secrets_equal("secret", "secret"));
+        assert!(!role_secrets_equal("secret", "secreT"));
+        assert!(!role_secrets_equal("secret", "Secret"));
+        assert!(!role_secrets_equal("secret", "secret2"));
+        assert!(!role_secrets_equal("secret", ""));
+    }
+}
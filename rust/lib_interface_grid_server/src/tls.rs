@@ -0,0 +1,123 @@
+use std::error::Error;
+use std::fs::read;
+use std::path::PathBuf;
+use tonic::transport::{Certificate, ClientTlsConfig, Endpoint, Identity, ServerTlsConfig};
+
+/// The usage of the TLS options on the command lines.
+pub const TLS_USAGE: &str =
+    "[--tls-ca-certificate <PATH>] [--tls-certificate <PATH>] [--tls-key <PATH>]";
+
+/// The PEM files for TLS connections, as given on the command line.
+///
+/// A server uses TLS if its certificate is given and additionally verifies the certificates of the
+/// clients (mutual TLS) if a CA certificate is given. A client uses TLS if a CA certificate is
+/// given to verify the server's certificate and additionally presents its own certificate if it is
+/// given.
+#[derive(Clone, Debug, Default)]
+pub struct TlsPaths {
+    /// The certificate of the authority that signed the certificates of the other side.
+    pub ca_certificate_path: Option<PathBuf>,
+    /// The own certificate.
+    pub certificate_path: Option<PathBuf>,
+    /// The private key of the own certificate.
+    pub key_path: Option<PathBuf>,
+}
+
+impl TlsPaths {
+    /// Sets the path of the given TLS option, which is one of "--tls-ca-certificate",
+    /// "--tls-certificate" and "--tls-key".
+    pub fn set(&mut self, option: &str, path: &str) -> Result<(), String> {
+        let path = Some(PathBuf::from(path));
+
+        match option {
+            "--tls-ca-certificate" => self.ca_certificate_path = path,
+            "--tls-certificate" => self.certificate_path = path,
+            "--tls-key" => self.key_path = path,
+            _ => return Err(format!("Unknown TLS option \"{option}\"")),
+        }
+
+        Ok(())
+    }
+
+    /// Removes the TLS options and their paths from the given command line arguments and returns
+    /// them.
+    pub fn take_from_command_line_arguments(
+        command_line_arguments: &mut Vec<String>,
+    ) -> Result<TlsPaths, String> {
+        let mut tls_paths = TlsPaths::default();
+        let mut all_command_line_arguments = std::mem::take(command_line_arguments).into_iter();
+
+        while let Some(command_line_argument) = all_command_line_arguments.next() {
+            if !command_line_argument.starts_with("--tls-") {
+                command_line_arguments.push(command_line_argument);
+                continue;
+            }
+
+            let path = all_command_line_arguments
+                .next()
+                .ok_or_else(|| format!("Missing value for \"{command_line_argument}\""))?;
+
+            tls_paths.set(&command_line_argument, &path)?;
+        }
+
+        Ok(tls_paths)
+    }
+
+    /// Returns the TLS configuration of a server, if its certificate is given.
+    pub fn server_tls_config(&self) -> Result<Option<ServerTlsConfig>, Box<dyn Error>> {
+        let Some(identity) = self.identity()? else {
+            return Ok(None);
+        };
+
+        let mut server_tls_config = ServerTlsConfig::new().identity(identity);
+
+        // Require the clients to present a certificate signed by the given authority.
+        if let Some(ca_certificate_path) = &self.ca_certificate_path {
+            server_tls_config =
+                server_tls_config.client_ca_root(Certificate::from_pem(read(ca_certificate_path)?));
+        }
+
+        Ok(Some(server_tls_config))
+    }
+
+    /// Returns the TLS configuration of a client, if a CA certificate is given.
+    pub fn client_tls_config(&self) -> Result<Option<ClientTlsConfig>, Box<dyn Error>> {
+        let Some(ca_certificate_path) = &self.ca_certificate_path else {
+            return Ok(None);
+        };
+
+        let mut client_tls_config = ClientTlsConfig::new()
+            .ca_certificate(Certificate::from_pem(read(ca_certificate_path)?));
+
+        // Present the client's certificate for mutual TLS.
+        if let Some(identity) = self.identity()? {
+            client_tls_config = client_tls_config.identity(identity);
+        }
+
+        Ok(Some(client_tls_config))
+    }
+
+    /// Returns the endpoint of the server at the given address, which is reached over TLS if a CA
+    /// certificate is given.
+    pub fn endpoint(&self, server_address: &str) -> Result<Endpoint, Box<dyn Error>> {
+        match self.client_tls_config()? {
+            Some(client_tls_config) => {
+                Ok(Endpoint::from_shared(format!("https://{server_address}"))?
+                    .tls_config(client_tls_config)?)
+            }
+            None => Ok(Endpoint::from_shared(format!("http://{server_address}"))?),
+        }
+    }
+
+    /// Returns the own certificate together with its key, if given.
+    fn identity(&self) -> Result<Option<Identity>, Box<dyn Error>> {
+        match (&self.certificate_path, &self.key_path) {
+            (Some(certificate_path), Some(key_path)) => Ok(Some(Identity::from_pem(
+                read(certificate_path)?,
+                read(key_path)?,
+            ))),
+            (None, None) => Ok(None),
+            _ => Err("The TLS certificate and key must be given together".into()),
+        }
+    }
+}